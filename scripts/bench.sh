@@ -0,0 +1 @@
+source ~/Projects/synth/scripts/env.sh && cargo run --release --bin render_bench
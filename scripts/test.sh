@@ -0,0 +1,3 @@
+# Host unit tests for the DSP core: no board and no esp toolchain needed.
+# The device-only modules (controls, hardware, flash) are compiled out on the host.
+cargo +stable test --lib --target "$(rustc +stable -vV | sed -n 's/^host: //p')" "$@"
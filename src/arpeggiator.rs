@@ -0,0 +1,186 @@
+//! Arpeggiator: steps through the active voices one at a time.
+//!
+//! Clocked by rendered sample count rather than a timer task, so step
+//! timing is locked to the audio clock and never drifts; block rendering
+//! splits at step boundaries, so every step lands on its exact sample.
+
+use crate::config::{ARP_DEFAULT_STEP_MS, ARP_STEP_MAX_MS, ARP_STEP_MIN_MS, VOICE_COUNT};
+
+/// Order in which the arpeggiator visits the active voices (sorted by pitch).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ArpPattern {
+    /// Lowest to highest, then wrap
+    Up,
+    /// Highest to lowest, then wrap
+    Down,
+    /// Lowest to highest and back, without repeating the end points
+    UpDown,
+}
+
+/// Step clock and pattern position.
+pub struct Arpeggiator {
+    /// Whether the arpeggiator gates the voices (false = all active voices sound)
+    enabled: bool,
+    /// Visiting order
+    pattern: ArpPattern,
+    /// Sample rate in Hz (for converting step length from ms)
+    sample_rate: f32,
+    /// Step length in samples
+    step_samples: u32,
+    /// Samples elapsed in the current step
+    elapsed: u32,
+    /// Position in the pitch-sorted voice order
+    position: usize,
+    /// Current UpDown direction
+    ascending: bool,
+}
+
+impl Arpeggiator {
+    /// Create a disabled arpeggiator (Up pattern, ARP_DEFAULT_STEP_MS steps).
+    ///
+    /// # Arguments
+    /// * `sample_rate` - Audio sample rate in Hz
+    pub fn new(sample_rate: f32) -> Self {
+        let mut arp = Self {
+            enabled: false,
+            pattern: ArpPattern::Up,
+            sample_rate,
+            step_samples: 1,
+            elapsed: 0,
+            position: 0,
+            ascending: true,
+        };
+        arp.set_step_ms(ARP_DEFAULT_STEP_MS);
+        arp
+    }
+
+    /// Whether the arpeggiator is gating the voices.
+    pub fn enabled(&self) -> bool {
+        self.enabled
+    }
+
+    /// Enable or disable; enabling restarts the pattern from its first step.
+    pub fn set_enabled(&mut self, enabled: bool) {
+        if enabled && !self.enabled {
+            self.restart();
+        }
+        self.enabled = enabled;
+    }
+
+    /// Select the visiting order (takes effect from the next step).
+    pub fn set_pattern(&mut self, pattern: ArpPattern) {
+        self.pattern = pattern;
+    }
+
+    /// Set the step length in ms (clamped to ARP_STEP_MIN_MS..=ARP_STEP_MAX_MS).
+    pub fn set_step_ms(&mut self, step_ms: f32) {
+        let step_ms = step_ms.clamp(ARP_STEP_MIN_MS, ARP_STEP_MAX_MS);
+        self.step_samples = ((step_ms * 0.001 * self.sample_rate) as u32).max(1);
+        self.elapsed = self.elapsed.min(self.step_samples - 1);
+    }
+
+    /// Advance the clock by `frames` samples.
+    ///
+    /// Leftover samples carry into the next step, so the average step length
+    /// is exact regardless of how frames are grouped.
+    ///
+    /// # Returns
+    /// true if a step boundary was crossed (the caller should pick the next voice)
+    pub fn advance(&mut self, frames: u32) -> bool {
+        if !self.enabled {
+            return false;
+        }
+        self.elapsed += frames;
+        if self.elapsed < self.step_samples {
+            return false;
+        }
+        self.elapsed %= self.step_samples;
+        true
+    }
+
+    /// Samples that can be clocked before the next step boundary is crossed.
+    ///
+    /// # Returns
+    /// None while disabled (no step is ever due)
+    pub fn frames_until_step(&self) -> Option<u32> {
+        self.enabled.then(|| self.step_samples - 1 - self.elapsed)
+    }
+
+    /// Pick the voice for the next step.
+    ///
+    /// # Arguments
+    /// * `order` - Indices of the active voices, sorted by ascending pitch
+    ///
+    /// # Returns
+    /// Voice index to sound, or None if no voice is active
+    pub fn next_voice(&mut self, order: &[u8]) -> Option<u8> {
+        let len = order.len();
+        if len == 0 {
+            return None;
+        }
+
+        // Voices may have switched off since the last step
+        self.position = self.position.min(len - 1);
+        let idx = match self.pattern {
+            ArpPattern::Down => len - 1 - self.position,
+            ArpPattern::Up | ArpPattern::UpDown => self.position,
+        };
+
+        self.position = match self.pattern {
+            ArpPattern::Up | ArpPattern::Down => (self.position + 1) % len,
+            ArpPattern::UpDown if len == 1 => 0,
+            ArpPattern::UpDown => {
+                if self.position == len - 1 {
+                    self.ascending = false;
+                } else if self.position == 0 {
+                    self.ascending = true;
+                }
+                if self.ascending {
+                    self.position + 1
+                } else {
+                    self.position - 1
+                }
+            }
+        };
+
+        Some(order[idx])
+    }
+
+    /// Restart from the first step of the pattern.
+    fn restart(&mut self) {
+        self.elapsed = 0;
+        self.position = 0;
+        self.ascending = true;
+    }
+}
+
+/// Indices of the active voices sorted by ascending frequency.
+///
+/// # Arguments
+/// * `voices` - (active, frequency) of every voice, in voice order
+///
+/// # Returns
+/// Index buffer and the number of valid entries
+pub fn pitch_order(voices: impl Iterator<Item = (bool, f32)>) -> ([u8; VOICE_COUNT], usize) {
+    let mut order = [0u8; VOICE_COUNT];
+    let mut freqs = [0.0f32; VOICE_COUNT];
+    let mut len = 0;
+
+    // Insertion sort: VOICE_COUNT is tiny and this runs once per step
+    for (idx, (active, freq)) in voices.enumerate().take(VOICE_COUNT) {
+        if !active {
+            continue;
+        }
+        let mut pos = len;
+        while pos > 0 && freqs[pos - 1] > freq {
+            order[pos] = order[pos - 1];
+            freqs[pos] = freqs[pos - 1];
+            pos -= 1;
+        }
+        order[pos] = idx as u8;
+        freqs[pos] = freq;
+        len += 1;
+    }
+
+    (order, len)
+}
@@ -0,0 +1,126 @@
+//! Output sample formats and f32 → PCM conversion helpers for the I2S path.
+
+/// Full-scale value of a signed 16-bit sample.
+const I16_MAX_F32: f32 = i16::MAX as f32;
+
+/// Full-scale value of a signed 24-bit sample (2^23 - 1).
+const I24_MAX_F32: f32 = 8_388_607.0;
+
+/// Largest frame produced by any `OutputFormat` (Stereo24: 2 × 4 bytes).
+pub const MAX_FRAME_SIZE: usize = 8;
+
+/// PCM layout written into the DMA buffer for every frame.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OutputFormat {
+    /// 16-bit samples in 16-bit slots (4-byte stereo frame)
+    Stereo16,
+
+    /// 24-bit samples MSB-aligned in 32-bit slots (8-byte stereo frame)
+    Stereo24,
+
+    /// One 16-bit sample per frame (2 bytes), duplicated to both slots by the I2S peripheral
+    Mono16,
+}
+
+impl OutputFormat {
+    /// Bytes occupied by one channel sample in the DMA buffer.
+    pub const fn bytes_per_sample(self) -> usize {
+        match self {
+            OutputFormat::Stereo16 | OutputFormat::Mono16 => 2,
+            OutputFormat::Stereo24 => 4,
+        }
+    }
+
+    /// Number of samples written per frame.
+    pub const fn channel_count(self) -> usize {
+        match self {
+            OutputFormat::Stereo16 | OutputFormat::Stereo24 => 2,
+            OutputFormat::Mono16 => 1,
+        }
+    }
+
+    /// Whether the I2S peripheral must run in mono mode (one sample feeds both slots).
+    pub const fn is_mono(self) -> bool {
+        self.channel_count() == 1
+    }
+
+    /// Bytes occupied by one frame (one sample for every channel).
+    pub const fn frame_size(self) -> usize {
+        self.bytes_per_sample() * self.channel_count()
+    }
+}
+
+const _: () = assert!(
+    OutputFormat::Stereo16.frame_size() <= MAX_FRAME_SIZE
+        && OutputFormat::Stereo24.frame_size() <= MAX_FRAME_SIZE
+        && OutputFormat::Mono16.frame_size() <= MAX_FRAME_SIZE,
+    "MAX_FRAME_SIZE must cover every output format"
+);
+
+/// Convert a normalized sample (-1.0 to 1.0) to little-endian `i16` bytes.
+///
+/// Input is clamped to ±1.0, so overshoot from voice sums or effects
+/// saturates symmetrically at ±`i16::MAX` in every build profile.
+/// NaN survives the clamp and the saturating cast maps it to 0 (silence).
+#[inline(always)]
+pub fn f32_to_i16_le(sample: f32) -> [u8; 2] {
+    ((sample.clamp(-1.0, 1.0) * I16_MAX_F32) as i16).to_le_bytes()
+}
+
+/// Convert a normalized sample (-1.0 to 1.0) to a 24-bit value packed in a
+/// little-endian 32-bit slot.
+///
+/// The 24 significant bits are MSB-aligned (low byte is zero), which is what
+/// the I2S peripheral expects for 24-bit data in 32-bit channels.
+/// Input is clamped to ±1.0 so it can never wrap into the sign bit; NaN maps to 0.
+#[inline(always)]
+pub fn f32_to_i24_le(sample: f32) -> [u8; 4] {
+    let value = (sample.clamp(-1.0, 1.0) * I24_MAX_F32) as i32;
+    (value << 8).to_le_bytes()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn i16_clamps_out_of_range_input() {
+        assert_eq!(f32_to_i16_le(2.0), i16::MAX.to_le_bytes());
+        assert_eq!(f32_to_i16_le(-2.0), (-i16::MAX).to_le_bytes());
+        assert_eq!(f32_to_i16_le(f32::INFINITY), i16::MAX.to_le_bytes());
+        assert_eq!(f32_to_i16_le(f32::NEG_INFINITY), (-i16::MAX).to_le_bytes());
+    }
+
+    #[test]
+    fn i16_maps_nan_to_silence() {
+        assert_eq!(f32_to_i16_le(f32::NAN), [0, 0]);
+    }
+
+    #[test]
+    fn i16_in_range_values_are_scaled_and_le() {
+        assert_eq!(f32_to_i16_le(0.0), [0x00, 0x00]);
+        assert_eq!(f32_to_i16_le(1.0), [0xFF, 0x7F]);
+        assert_eq!(f32_to_i16_le(-1.0), [0x01, 0x80]);
+        assert_eq!(i16::from_le_bytes(f32_to_i16_le(0.5)), 16_383);
+    }
+
+    #[test]
+    fn i24_is_msb_aligned_in_a_le_slot() {
+        // Low byte is padding; the 24 significant bits follow, least significant first
+        assert_eq!(f32_to_i24_le(0.0), [0x00, 0x00, 0x00, 0x00]);
+        assert_eq!(f32_to_i24_le(1.0), [0x00, 0xFF, 0xFF, 0x7F]);
+        assert_eq!(f32_to_i24_le(-1.0), [0x00, 0x01, 0x00, 0x80]);
+        assert_eq!(f32_to_i24_le(1.0 / I24_MAX_F32), [0x00, 0x01, 0x00, 0x00]);
+        assert_eq!(f32_to_i24_le(-1.0 / I24_MAX_F32), [0x00, 0xFF, 0xFF, 0xFF]);
+    }
+
+    #[test]
+    fn i24_keeps_the_sign_and_saturates() {
+        let decode = |bytes| i32::from_le_bytes(bytes) >> 8;
+        assert_eq!(decode(f32_to_i24_le(0.5)), 4_194_303);
+        assert_eq!(decode(f32_to_i24_le(-0.5)), -4_194_303);
+        assert_eq!(f32_to_i24_le(2.0), f32_to_i24_le(1.0));
+        assert_eq!(f32_to_i24_le(-2.0), f32_to_i24_le(-1.0));
+        assert_eq!(f32_to_i24_le(f32::NAN), [0; 4]);
+    }
+}
@@ -12,14 +12,81 @@ use embassy_executor::Spawner;
 use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex as ChannelMutex;
 use embassy_sync::channel::Channel;
 use esp_backtrace as _;
-use esp_hal::{dma_circular_buffers, gpio::{Input, InputConfig, Pull}, timer::timg::TimerGroup};
-use synth::{config::*, controls::button_task, engine::Engine, hardware, message::Message};
+use esp_hal::{
+    dma_circular_buffers,
+    gpio::{Input, InputConfig, Pull},
+    peripherals::{GPIO1, GPIO2},
+    timer::timg::TimerGroup,
+};
+use esp_storage::FlashStorage;
+use static_cell::StaticCell;
+use synth::{
+    config::*,
+    controls::{
+        button_task, led_task, poll_pots, signal_button_task, CalibrateSignal, CtrlSender,
+        PotButtons, RebindSignal,
+    },
+    engine::{Engine, UserWavetables, WavetableStaging},
+    hardware::{self, AdcBus, PotPin},
+    message::Message,
+    meter::VoiceLevels,
+    oscillator::SINE,
+    preset::{
+        preset_task, CalibrationState, FlashMute, LoadedPreset, PresetRequests, PresetStore,
+    },
+};
 
 esp_bootloader_esp_idf::esp_app_desc!();
 
 /// Global MPSC channel for control → audio communication
 static CHANNEL: Channel<ChannelMutex, Message, MESSAGE_QUEUE_SIZE> = Channel::new();
 
+/// Rebind button → pot task (cycles the second pot's parameter)
+static REBIND: RebindSignal = RebindSignal::new();
+
+/// Calibrate button → pot task (starts, then finishes and saves, pot calibration)
+static CALIBRATE: CalibrateSignal = CalibrateSignal::new();
+
+/// Per-voice output levels (engine → LED task)
+static VOICE_LEVELS: VoiceLevels = VoiceLevels::new();
+
+/// Engine → preset task flash requests, and snapshots read back
+static PRESET_REQUESTS: PresetRequests = PresetRequests::new();
+static LOADED_PRESET: LoadedPreset = LoadedPreset::new();
+
+/// Preset task ↔ engine handshake that silences the output around flash writes
+static FLASH_MUTE: FlashMute = FlashMute::new();
+
+/// User wavetable storage (too large to live in the main task's future)
+static USER_WAVETABLES: StaticCell<UserWavetables> = StaticCell::new();
+
+/// Control task → engine hand-off for runtime wavetable loads (see Message::LoadWavetable)
+static WAVETABLE_STAGING: WavetableStaging = WavetableStaging::new();
+
+/// Pot polling on this board's pot pins (GPIO1 = frequency, GPIO2 = second pot).
+///
+/// The library loop is generic over the pins; this wrapper fixes them for the task.
+#[embassy_executor::task]
+async fn pot_task(
+    sender: CtrlSender,
+    adc_bus: AdcBus,
+    freq_pin: PotPin<GPIO1<'static>>,
+    vol_pin: PotPin<GPIO2<'static>>,
+    buttons: PotButtons,
+    calibration: CalibrationState,
+) {
+    poll_pots(
+        sender,
+        adc_bus,
+        freq_pin,
+        vol_pin,
+        buttons,
+        calibration,
+        &PRESET_REQUESTS,
+    )
+    .await
+}
+
 #[esp_hal_embassy::main]
 async fn main(spawner: Spawner) {
     // Initialize logger
@@ -50,9 +117,14 @@ async fn main(spawner: Spawner) {
     sender.send(Message::SelectVoice(0)).await;
     sender.send(Message::SetVolume(1.0)).await;
 
-    let mut engine = Engine::new(SAMPLE_RATE as f32, receiver);
+    let mut engine = Engine::new(SAMPLE_RATE as f32, OUTPUT_FORMAT, receiver);
+    engine.set_preset_channels(&PRESET_REQUESTS, &LOADED_PRESET, &FLASH_MUTE);
+    engine.set_level_meter(&VOICE_LEVELS);
+    // Slots start as sine, so Custom(n) is audible before anything is loaded
+    engine.set_user_wavetables(USER_WAVETABLES.init_with(|| [SINE; USER_WAVETABLE_SLOTS]));
+    engine.set_wavetable_staging(&WAVETABLE_STAGING);
 
-    // Initialize I2S audio hardware
+    // Initialize I2S audio hardware (BCLK = GPIO7, WS = GPIO8, DOUT = GPIO9)
     #[allow(clippy::manual_div_ceil)]
     let (_, _, tx_buffer, tx_descriptors) = dma_circular_buffers!(0, DMA_BUFFER_SIZE);
     let mut audio_stream = hardware::setup_audio(
@@ -61,6 +133,8 @@ async fn main(spawner: Spawner) {
         peripherals.GPIO7,
         peripherals.GPIO8,
         peripherals.GPIO9,
+        SAMPLE_RATE,
+        OUTPUT_FORMAT,
         tx_buffer,
         tx_descriptors,
     );
@@ -72,8 +146,37 @@ async fn main(spawner: Spawner) {
         peripherals.GPIO2,
     );
 
+    // Pot ranges from the last calibration (read before the preset task takes the flash)
+    let mut presets = PresetStore::new(FlashStorage::new());
+    let calibration = presets.load_calibration().unwrap_or(CalibrationState::DEFAULT);
+
     // Spawn pot task to read both potentiometers
-    spawner.spawn(synth::controls::pot_task(sender, adc_bus, freq_pin, vol_pin)).unwrap();
+    let buttons = PotButtons {
+        rebind: &REBIND,
+        calibrate: &CALIBRATE,
+    };
+    spawner.spawn(pot_task(sender, adc_bus, freq_pin, vol_pin, buttons, calibration)).unwrap();
+
+    // Preset task owns the flash, so saves and loads never run inside render();
+    // writes still stall this executor, so the engine fades out around each one
+    spawner
+        .spawn(preset_task(presets, &PRESET_REQUESTS, &LOADED_PRESET, &FLASH_MUTE))
+        .unwrap();
+
+    // Rebind button on GPIO6 cycles the second pot: volume → cutoff → resonance
+    let rebind_btn = Input::new(peripherals.GPIO6, InputConfig::default().with_pull(Pull::Up));
+    spawner.spawn(signal_button_task(rebind_btn, &REBIND)).unwrap();
+
+    // Calibrate button on GPIO13: press, sweep both pots end to end, press again
+    let calibrate_btn = Input::new(peripherals.GPIO13, InputConfig::default().with_pull(Pull::Up));
+    spawner.spawn(signal_button_task(calibrate_btn, &CALIBRATE)).unwrap();
+
+    // Voice LEDs on GPIO10, GPIO11, GPIO12 (brightness follows each voice's level)
+    let leds = hardware::setup_leds(
+        peripherals.LEDC,
+        [peripherals.GPIO10.into(), peripherals.GPIO11.into(), peripherals.GPIO12.into()],
+    );
+    spawner.spawn(led_task(&VOICE_LEVELS, leds)).unwrap();
 
     // Audio rendering loop
     loop {
@@ -0,0 +1,90 @@
+//! Render benchmark: CCOUNT cycles per frame of the per-sample and block render paths.
+//!
+//! Flash and run on the board (`./scripts/bench.sh`); results print over the
+//! console. The per-sample path calls `Engine::tick()` once per frame and
+//! converts it to i16, the way `render()` worked before block rendering. It
+//! skips the stereo width stage, so it slightly flatters the per-sample side.
+
+#![no_std]
+#![no_main]
+
+use esp_backtrace as _;
+use esp_hal::xtensa_lx::timer::get_cycle_count;
+use esp_println::println;
+use synth::{
+    audio_util::{f32_to_i16_le, OutputFormat},
+    config::SAMPLE_RATE,
+    engine::Engine,
+    message::{Message, QueueSource},
+};
+
+esp_bootloader_esp_idf::esp_app_desc!();
+
+/// Bytes per Stereo16 frame.
+const FRAME_BYTES: usize = 4;
+
+/// Frames rendered per measured buffer.
+const BENCH_FRAMES: usize = 512;
+
+/// Buffers measured per path, after the warm-up.
+const BENCH_BUFFERS: u32 = 200;
+
+/// Buffers rendered before measuring, so fade-ins finish first
+/// (the block path falls back to per-sample rendering while a voice fades).
+const WARMUP_BUFFERS: u32 = 8;
+
+#[esp_hal::main]
+fn main() -> ! {
+    // Same clock configuration as the synth binary
+    let _peripherals = esp_hal::init(esp_hal::Config::default());
+    let mut buffer = [0u8; BENCH_FRAMES * FRAME_BYTES];
+
+    for voices in [1, 3] {
+        let per_sample = measure(voices, &mut buffer, |engine, buffer| {
+            for frame in buffer.chunks_exact_mut(FRAME_BYTES) {
+                let bytes = f32_to_i16_le(engine.tick());
+                frame[..2].copy_from_slice(&bytes);
+                frame[2..].copy_from_slice(&bytes);
+            }
+        });
+        let block = measure(voices, &mut buffer, |engine, buffer| {
+            engine.render(buffer);
+        });
+        println!(
+            "{} voice(s): per-sample {} cycles/frame, block {} cycles/frame",
+            voices, per_sample, block
+        );
+    }
+
+    loop {
+        core::hint::spin_loop();
+    }
+}
+
+/// Average CCOUNT cycles per frame of `render_buffer` over BENCH_BUFFERS buffers.
+///
+/// # Arguments
+/// * `voices` - Number of voices switched on
+/// * `buffer` - Stereo16 output buffer, reused for every render
+/// * `render_buffer` - Render path under test
+fn measure(
+    voices: u8,
+    buffer: &mut [u8],
+    mut render_buffer: impl FnMut(&mut Engine<QueueSource<1>>, &mut [u8]),
+) -> u32 {
+    let mut engine = Engine::new(SAMPLE_RATE as f32, OutputFormat::Stereo16, QueueSource::new());
+    for idx in 0..voices {
+        engine.process_message(Message::ToggleVoice(idx));
+    }
+    for _ in 0..WARMUP_BUFFERS {
+        render_buffer(&mut engine, buffer);
+    }
+
+    // CCOUNT wraps after 2^32 cycles; a measurement takes well under that
+    let start = get_cycle_count();
+    for _ in 0..BENCH_BUFFERS {
+        render_buffer(&mut engine, buffer);
+    }
+    let cycles = get_cycle_count().wrapping_sub(start);
+    cycles / (BENCH_BUFFERS * (buffer.len() / FRAME_BYTES) as u32)
+}
@@ -1,6 +1,8 @@
 //! Centralized configuration constants for the synth.
 //! All magic numbers should live here to ensure consistency.
 
+use crate::audio_util::OutputFormat;
+
 // === Synth Engine ===
 
 /// Number of simultaneously mixable voices.
@@ -23,6 +25,21 @@ pub const STARTING_FREQUENCY: f32 = 77.0;
 /// Default volume level for voices (0.0 to 1.0).
 pub const DEFAULT_VOICE_VOLUME: f32 = 0.9;
 
+/// Amplitude scale at the softest NoteOn velocity (0.01 ≈ -40 dB).
+/// Keeps velocity 1 quiet but audible instead of vanishing under the v² curve.
+pub const VELOCITY_MIN_GAIN: f32 = 0.01;
+
+/// Voice on/off ramp length (ms); long enough to avoid pops, short enough to feel instant.
+pub const FADE_MS: f32 = 5.0;
+
+// === Pitch ===
+
+/// Reference tuning for MIDI note 69 (A4) in Hz.
+pub const TUNING_A4_HZ: f32 = 440.0;
+
+/// Pitch bend range in semitones for a full wheel deflection (±1.0).
+pub const PITCH_BEND_RANGE_SEMITONES: f32 = 2.0;
+
 // === Output Level Management ===
 
 /// Minimum level in decibels for metering and UI.
@@ -35,30 +52,166 @@ pub const MAX_DB: f32 = 0.0;
 /// Provides headroom even when all voices are at max volume (0.95 ≈ -0.45 dB).
 pub const MASTER_GAIN: f32 = 0.85;
 
+/// Normalized mix level above which the limiter reduces gain (before MASTER_GAIN).
+/// Just under full scale: everyday levels pass untouched, while full-volume peaks
+/// and resonance overshoot past 1.0 are held at this level.
+pub const LIMITER_THRESHOLD: f32 = 0.9;
+
+/// Limiter attack time (ms): how fast gain backs off when the mix exceeds the threshold.
+pub const LIMITER_ATTACK_MS: f32 = 1.0;
+
+/// Limiter release time (ms): how fast gain recovers to unity afterwards.
+pub const LIMITER_RELEASE_MS: f32 = 150.0;
+
+// === Oscillator ===
+
+/// Narrowest square-wave pulse width (fraction of the cycle spent high).
+pub const PULSE_WIDTH_MIN: f32 = 0.05;
+
+/// Widest square-wave pulse width (fraction of the cycle spent high).
+pub const PULSE_WIDTH_MAX: f32 = 0.95;
+
+// === Filter ===
+
+/// Lowest voice filter cutoff (Hz).
+pub const FILTER_CUTOFF_MIN: f32 = 40.0;
+
+/// Highest voice filter cutoff (Hz); at this cutoff with no resonance the filter is bypassed.
+/// Stays well below Nyquist so the warped gain (tan) remains accurate.
+pub const FILTER_CUTOFF_MAX: f32 = 16_000.0;
+
+/// Playing frequency at which key tracking leaves the cutoff unchanged (middle C, Hz).
+pub const KEY_TRACK_REFERENCE_HZ: f32 = 261.63;
+
+/// Fraction of the damping removed at full resonance (0.9 → Q = 5, +14 dB peak at cutoff).
+pub const FILTER_RESONANCE_MAX: f32 = 0.9;
+
+// === Effects ===
+
+/// Tremolo LFO rate on startup (Hz).
+pub const TREMOLO_DEFAULT_RATE_HZ: f32 = 5.0;
+
+/// Slowest tremolo LFO rate (Hz).
+pub const TREMOLO_RATE_MIN: f32 = 0.1;
+
+/// Fastest tremolo LFO rate (Hz).
+pub const TREMOLO_RATE_MAX: f32 = 20.0;
+
+/// Bitcrusher bit depth treated as "no quantization" (matches 16-bit output).
+pub const BITCRUSHER_MAX_BITS: u8 = 16;
+
+/// Longest bitcrusher sample-hold length (44.1 kHz / 32 ≈ 1.4 kHz effective rate).
+pub const BITCRUSHER_MAX_DOWNSAMPLE: u8 = 32;
+
+/// Widest stereo width setting (2.0 = side doubled); keeps widening from running away.
+pub const STEREO_WIDTH_MAX: f32 = 2.0;
+
+/// Corner frequency of the master DC blocker (Hz).
+/// 5 Hz gives R ≈ 0.9993 at 44.1 kHz: flat in the audio band, offsets settle in ~0.1 s.
+pub const DC_BLOCKER_CUTOFF_HZ: f32 = 5.0;
+
+// === Arpeggiator ===
+
+/// Arpeggiator step length on startup (ms); 125 ms = 16th notes at 120 BPM.
+pub const ARP_DEFAULT_STEP_MS: f32 = 125.0;
+
+/// Shortest arpeggiator step (ms); keeps steps well above the render block length.
+pub const ARP_STEP_MIN_MS: f32 = 20.0;
+
+/// Longest arpeggiator step (ms).
+pub const ARP_STEP_MAX_MS: f32 = 2000.0;
+
 // === Wavetable ===
 
-/// Wavetable size (must remain a power of two for fast wrapping).
-pub const WAVETABLE_SIZE: usize = 1024;
+/// Number of phase bits used to index the wavetable (log2 of WAVETABLE_SIZE).
+pub const WAVETABLE_BITS: u32 = 10;
 
-/// Wavetable size as f32 (cached for hot paths to avoid repeated casts).
-pub const WAVETABLE_SIZE_F32: f32 = WAVETABLE_SIZE as f32;
+/// Wavetable size (must remain a power of two for fast wrapping).
+pub const WAVETABLE_SIZE: usize = 1 << WAVETABLE_BITS;
 
 /// Wavetable index mask for rapid wrapping (SIZE - 1, valid because SIZE is a power of two).
 pub const WAVETABLE_MASK: usize = WAVETABLE_SIZE - 1;
 
+// === Audio Output Format ===
+
+/// PCM layout sent to the DAC (sample width, channel count and I2S data format).
+/// `Stereo24` lowers the noise floor at the cost of twice the DMA bandwidth;
+/// `Mono16` halves the DMA bandwidth for mono amplifiers.
+pub const OUTPUT_FORMAT: OutputFormat = OutputFormat::Stereo16;
+
+/// Number of user wavetable slots selectable via `Waveform::Custom`.
+/// Each slot is WAVETABLE_SIZE f32s (4 KB).
+pub const USER_WAVETABLE_SLOTS: usize = 2;
+
 // === DMA & Streaming ===
 
+/// Frames rendered per DSP block before conversion to PCM bytes.
+/// Larger blocks amortize per-block overhead; 64 frames keeps the f32 scratch at 256 bytes of stack.
+pub const RENDER_BLOCK_SIZE: usize = 64;
+
+/// DMA circular buffer length in frames.
+/// Sized in frames so the byte size stays aligned for every output format.
+pub const DMA_BUFFER_FRAMES: usize = 511; // 511 frames = 2044 bytes of 16-bit stereo
+
 /// DMA circular buffer size in bytes.
-/// Must be divisible by 4 (stereo `i16` frame size) to keep channels aligned.
-pub const DMA_BUFFER_SIZE: usize = 2044; // 1024, 2044 is solid
+/// Must be divisible by the frame size to keep channels aligned.
+pub const DMA_BUFFER_SIZE: usize = DMA_BUFFER_FRAMES * OUTPUT_FORMAT.frame_size();
+
+const _: () = assert!(
+    DMA_BUFFER_SIZE % OUTPUT_FORMAT.frame_size() == 0,
+    "DMA_BUFFER_SIZE must be a multiple of the output frame size"
+);
 
 // === Messaging ===
 
 /// Capacity of the control message queue.
 pub const MESSAGE_QUEUE_SIZE: usize = 8;
 
+/// The control queue counts as nearly full when this many slots or fewer are free.
+///
+/// Continuous messages (pot values) are held back and coalesced while the
+/// queue is nearly full, so these last slots stay free for discrete messages
+/// (toggles, notes, presets) and a pot sweep can never starve a NoteOff.
+/// With a queue of 8 and a threshold of 2: pots may fill 6 slots, then wait;
+/// discrete messages can always queue 2 more. Worst-case control latency is
+/// therefore bounded by one drain of the queue per render call.
+pub const MESSAGE_QUEUE_NEARLY_FULL: usize = 2;
+
+const _: () = assert!(
+    MESSAGE_QUEUE_NEARLY_FULL < MESSAGE_QUEUE_SIZE,
+    "continuous messages need at least one unreserved queue slot"
+);
+
+// === Presets ===
+
+/// Flash offset of the preset region.
+/// Uses the default partition table's `nvs` partition (0x9000, 24 KB), which is otherwise unused.
+pub const PRESET_FLASH_OFFSET: u32 = 0x9000;
+
+/// Bytes reserved per preset slot (must hold a serialized `EngineState`).
+pub const PRESET_SLOT_SIZE: u32 = 128;
+
+/// Number of preset slots available to SavePreset/LoadPreset.
+pub const PRESET_SLOT_COUNT: u8 = 4;
+
+/// Flash offset of the learned pot calibration, directly after the preset slots.
+pub const CALIBRATION_FLASH_OFFSET: u32 =
+    PRESET_FLASH_OFFSET + PRESET_SLOT_COUNT as u32 * PRESET_SLOT_SIZE;
+
+/// Capacity of the engine → preset task request queue.
+/// Requests beyond this while flash is busy are dropped with a warning.
+pub const PRESET_REQUEST_QUEUE_SIZE: usize = 2;
+
 // === Control & Input ===
 
+// --- Voice LEDs ---
+
+/// Voice LED refresh rate (Hz); well above flicker fusion, far below the audio rate.
+pub const LED_UPDATE_HZ: u64 = 60;
+
+/// LEDC PWM carrier frequency for the voice LEDs (kHz, 8-bit duty resolution).
+pub const LED_PWM_FREQUENCY_KHZ: u32 = 24;
+
 // --- ADC Sampling ---
 
 /// ADC polling interval in milliseconds.
@@ -73,16 +226,34 @@ pub const ADC_EMA_ALPHA: f32 = 0.6; // lower -> responsiveness
 
 // --- Potentiometer Scaling ---
 
+/// Number of potentiometers (frequency, second pot), in polling order.
+pub const POT_COUNT: usize = 2;
+
 /// Potentiometer minimum millivolt value.
 pub const POT_MIN: u16 = 0;
 
 /// Potentiometer maximum millivolt value (~3.156 V with 11 dB attenuation on ESP32-S3).
 pub const POT_MAX: u16 = 3156;
 
+/// Millivolts trimmed from each end of a learned pot range.
+/// Ensures the physical end stops reliably reach 0.0 and 1.0 despite noise.
+pub const POT_CALIBRATION_MARGIN: f32 = 20.0;
+
+/// Smallest learned pot range (mV) accepted by calibration.
+/// Narrower sweeps are assumed to be incomplete and are discarded.
+pub const POT_CALIBRATION_MIN_SPAN: f32 = 500.0;
+
 /// Normalized threshold before treating a potentiometer change as meaningful.
 /// 0.001 roughly maps to ~1 Hz increments across the usable range.
 pub const POT_CHANGE_THRESHOLD: f32 = 0.001;
 
+/// Slew limit that never engages (normalized values can't change by more than 1.0).
+pub const POT_NO_SLEW_LIMIT: f32 = 1.0;
+
+/// Largest normalized change of the frequency pot per poll.
+/// At 20 ms polls a full end-to-end sweep ramps over ~1 s instead of jumping.
+pub const POT_FREQ_MAX_SLEW_PER_POLL: f32 = 0.02;
+
 /// Exponent used when shaping the potentiometer response curve.
 pub const POT_EXPONENT_SCALE: i32 = 2;
 
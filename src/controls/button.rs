@@ -1,9 +1,8 @@
 //! Button input handling with async edge detection.
 
-use crate::controls::CtrlSender;
+use crate::controls::{send_prioritized, ButtonSignal, CtrlSender};
 use crate::message::Message;
 use esp_hal::gpio::Input;
-use log::warn;
 
 /// Button control task for a single voice.
 ///
@@ -25,11 +24,27 @@ pub async fn button_task(sender: CtrlSender, mut button: Input<'static>, voice_i
         button.wait_for_low().await;
 
         // Send selection message - Engine will handle toggle logic
-        if let Err(e) = sender.try_send(Message::ToggleVoice(voice_idx)) {
-            warn!("Button message dropped (queue full): {:?}", e);
-        }
+        // Discrete, so this waits for room rather than dropping (pots leave slots free)
+        send_prioritized(sender, Message::ToggleVoice(voice_idx)).await;
 
         // Wait for button release before accepting next press
         button.wait_for_high().await;
     }
 }
+
+/// Signal button task: the rebind and calibrate buttons.
+///
+/// Each press raises `signal`; the pot task picks it up on its next poll
+/// (see `poll_pots`), so the button never touches pot state directly.
+///
+/// # Arguments
+/// * `button` - GPIO input configured with pull-up (active-low)
+/// * `signal` - Signal shared with the pot task
+#[embassy_executor::task(pool_size = 2)]
+pub async fn signal_button_task(mut button: Input<'static>, signal: &'static ButtonSignal) {
+    loop {
+        button.wait_for_low().await;
+        signal.signal(());
+        button.wait_for_high().await;
+    }
+}
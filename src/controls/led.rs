@@ -0,0 +1,34 @@
+//! Voice activity LEDs: PWM brightness following each voice's output level.
+
+use crate::config::{LED_UPDATE_HZ, VOICE_COUNT};
+use crate::hardware::LedChannel;
+use crate::meter::VoiceLevels;
+use embassy_time::{Duration, Ticker};
+use esp_hal::ledc::channel::ChannelIFace;
+
+/// LED refresh task: maps each voice's level to its LED's PWM duty.
+///
+/// Runs at LED_UPDATE_HZ on a Ticker, so the audio path never touches the
+/// LEDC peripheral and the cost is a few register writes per frame.
+///
+/// # Arguments
+/// * `levels` - Levels published by the engine (see `Engine::set_level_meter`)
+/// * `leds` - One configured LEDC channel per voice, in voice order
+#[embassy_executor::task]
+pub async fn led_task(levels: &'static VoiceLevels, mut leds: [LedChannel; VOICE_COUNT]) {
+    let mut ticker = Ticker::every(Duration::from_hz(LED_UPDATE_HZ));
+    loop {
+        for (idx, led) in leds.iter_mut().enumerate() {
+            // Duty errors only occur for values > 100%, which brightness_pct never yields
+            led.set_duty(brightness_pct(levels.load(idx))).ok();
+        }
+        ticker.next().await;
+    }
+}
+
+/// Map a level (0.0 to 1.0) to a duty percentage with a squared curve,
+/// so low levels stay dim the way the eye expects.
+fn brightness_pct(level: f32) -> u8 {
+    let level = level.clamp(0.0, 1.0);
+    (level * level * 100.0 + 0.5) as u8
+}
@@ -5,18 +5,58 @@
 //! that sends messages to the audio engine via a shared channel.
 
 pub mod button;
+pub mod led;
 pub mod pot;
 pub mod task;
 
 // Re-export commonly used items
-pub use button::button_task;
-pub use pot::{map_freq, map_vol, Potentiometer};
-pub use task::pot_task;
+pub use crate::preset::PotCalibration;
+pub use button::{button_task, signal_button_task};
+pub use led::led_task;
+pub use pot::{map_cutoff, map_freq, map_resonance, map_vol, Potentiometer};
+pub use task::{poll_pots, PotButtons};
 
-use crate::config::MESSAGE_QUEUE_SIZE;
+use crate::config::{MESSAGE_QUEUE_NEARLY_FULL, MESSAGE_QUEUE_SIZE};
 use crate::message::Message;
 use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex as ChannelMutex;
 use embassy_sync::channel::Sender;
+use embassy_sync::signal::Signal;
 
 /// Type alias for control message sender (used across all control tasks)
 pub type CtrlSender = Sender<'static, ChannelMutex, Message, MESSAGE_QUEUE_SIZE>;
+
+/// Raised on each press of a signal button (see `signal_button_task`)
+pub type ButtonSignal = Signal<ChannelMutex, ()>;
+
+/// Raised by the rebind button; the pot task moves the second pot to its next mapping
+pub type RebindSignal = ButtonSignal;
+
+/// Raised by the calibrate button; the pot task starts, or finishes and saves, calibration
+pub type CalibrateSignal = ButtonSignal;
+
+/// Number of free slots in the control queue right now.
+pub fn free_slots(sender: CtrlSender) -> usize {
+    sender.free_capacity()
+}
+
+/// Whether the control queue has MESSAGE_QUEUE_NEARLY_FULL or fewer free slots.
+pub fn queue_nearly_full(sender: CtrlSender) -> bool {
+    free_slots(sender) <= MESSAGE_QUEUE_NEARLY_FULL
+}
+
+/// Send a message with priority by kind, never blocking on continuous values.
+///
+/// Continuous messages (see `Message::is_continuous`) are skipped while the
+/// queue is nearly full; the caller keeps the value and retries later.
+/// Discrete messages wait for a slot, which the threshold keeps available.
+///
+/// # Returns
+/// true if the message was queued
+pub async fn send_prioritized(sender: CtrlSender, msg: Message) -> bool {
+    if msg.is_continuous() {
+        !queue_nearly_full(sender) && sender.try_send(msg).is_ok()
+    } else {
+        sender.send(msg).await;
+        true
+    }
+}
@@ -1,9 +1,10 @@
 //! Potentiometer reading with EMA filtering and parameter mapping.
 
 use crate::config::*;
-use crate::controls::CtrlSender;
+use crate::controls::{send_prioritized, CtrlSender};
 use crate::hardware::PotPin;
 use crate::message::Message;
+use crate::preset::PotCalibration;
 use esp_hal::analog::adc::{Adc, AdcChannel};
 use esp_hal::peripherals::ADC1;
 use esp_hal::Blocking;
@@ -20,12 +21,24 @@ pub struct Potentiometer {
     filtered: f32,
     /// Filter coefficient (0.0 to 1.0, higher = more smoothing)
     alpha: f32,
-    /// Last sent normalized value (for deadband detection)
-    last_sent: f32,
+    /// Last sent normalized value (for deadband detection, None = send the next reading)
+    last_sent: Option<f32>,
+    /// Largest change of the normalized value per poll (>= 1.0 = unlimited)
+    max_slew_per_poll: f32,
+    /// Slew-limited normalized value (None until the first poll)
+    slewed: Option<f32>,
     /// Mapping function from normalized value to Message
     map_fn: fn(f32) -> Message,
     /// Sample buffer for multisampling (reused each poll)
     samples: [u16; ADC_MULTISAMPLING_COUNT],
+    /// Range used for normalization (learned or POT_MIN/POT_MAX)
+    calibration: PotCalibration,
+    /// Cached 1 / (max - min) for normalization
+    range_reciprocal: f32,
+    /// Extremes observed while calibrating (None = not calibrating)
+    observed: Option<PotCalibration>,
+    /// Latest mapped message not yet sent (superseded by newer readings, never queued twice)
+    pending: Option<Message>,
 }
 
 impl Potentiometer {
@@ -33,25 +46,90 @@ impl Potentiometer {
     ///
     /// # Arguments
     /// * `map_fn` - Function to map normalized value (0.0-1.0) to Message
-    pub fn new(map_fn: fn(f32) -> Message) -> Self {
+    /// * `max_slew_per_poll` - Largest normalized change emitted per poll; fast
+    ///   sweeps become a ramp of intermediate values (POT_NO_SLEW_LIMIT = off)
+    pub fn new(map_fn: fn(f32) -> Message, max_slew_per_poll: f32) -> Self {
         Self {
             filtered: ((POT_MIN + POT_MAX) / 2) as f32,
             alpha: ADC_EMA_ALPHA,
-            last_sent: 0.0,
+            last_sent: None,
+            max_slew_per_poll,
+            slewed: None,
             map_fn,
             samples: [0u16; ADC_MULTISAMPLING_COUNT],
+            calibration: PotCalibration::DEFAULT,
+            range_reciprocal: 1.0 / (PotCalibration::DEFAULT.max - PotCalibration::DEFAULT.min),
+            observed: None,
+            pending: None,
         }
     }
 
+    /// Rebind the pot to a different parameter.
+    ///
+    /// Clears the deadband state so the next reading is sent through the new
+    /// mapping even if the knob hasn't moved.
+    pub fn set_map_fn(&mut self, map_fn: fn(f32) -> Message) {
+        self.map_fn = map_fn;
+        self.last_sent = None;
+        self.pending = None;
+    }
+
+    /// Begin learning the pot's range; sweep it end to end, then call `finish_calibration`.
+    ///
+    /// Normalization keeps using the current range until calibration finishes.
+    pub fn start_calibration(&mut self) {
+        self.observed = Some(PotCalibration {
+            min: f32::MAX,
+            max: f32::MIN,
+        });
+    }
+
+    /// Stop learning and adopt the observed range, pulled in by POT_CALIBRATION_MARGIN.
+    ///
+    /// The margin guarantees both ends of the travel reach 0.0 and 1.0.
+    /// A sweep narrower than POT_CALIBRATION_MIN_SPAN is discarded.
+    ///
+    /// # Returns
+    /// true if the learned range was adopted
+    pub fn finish_calibration(&mut self) -> bool {
+        let Some(observed) = self.observed.take() else {
+            return false;
+        };
+
+        let learned = PotCalibration {
+            min: observed.min + POT_CALIBRATION_MARGIN,
+            max: observed.max - POT_CALIBRATION_MARGIN,
+        };
+        if learned.max - learned.min < POT_CALIBRATION_MIN_SPAN {
+            warn!("Pot calibration discarded: sweep too narrow");
+            return false;
+        }
+
+        self.set_calibration(learned);
+        true
+    }
+
+    /// Range currently used for normalization (e.g. to persist it, see `CalibrationState`).
+    pub fn calibration(&self) -> PotCalibration {
+        self.calibration
+    }
+
+    /// Apply a previously learned range.
+    pub fn set_calibration(&mut self, calibration: PotCalibration) {
+        self.calibration = calibration;
+        self.range_reciprocal = 1.0 / (calibration.max - calibration.min);
+    }
+
     /// Read, filter, and conditionally send message if value changed significantly.
     ///
     /// Performs complete signal chain:
     /// 1. Multisampling (reduces noise by √N)
     /// 2. Averaging
     /// 3. EMA filtering (smooth out remaining noise)
-    /// 4. Normalization (POT_MIN..POT_MAX → 0.0..1.0)
-    /// 5. Deadband check (only send if change >= threshold)
-    /// 6. Message mapping and send
+    /// 4. Normalization (calibrated min..max → 0.0..1.0)
+    /// 5. Slew limiting (at most max_slew_per_poll per poll)
+    /// 6. Deadband check (only send if change >= threshold)
+    /// 7. Message mapping and coalesced send (see `flush`)
     ///
     /// # Arguments
     /// * `sender` - Embassy channel sender
@@ -77,16 +155,52 @@ impl Potentiometer {
         // 3. Apply EMA filter: filtered = alpha * filtered + (1-alpha) * new
         self.filtered = self.filtered * self.alpha + avg * (1.0 - self.alpha);
 
+        // Track extremes while calibrating (filtered, so single noisy reads don't widen the range)
+        if let Some(observed) = self.observed.as_mut() {
+            observed.min = observed.min.min(self.filtered);
+            observed.max = observed.max.max(self.filtered);
+        }
+
         // 4. Normalize to 0.0-1.0 range using calibrated min/max, defensive clamping
-        let normalized = (self.filtered / (POT_MAX as f32)).clamp(0.0, 1.0);
-
-        // 5. Deadband check: only send if changed significantly
-        if (normalized - self.last_sent).abs() >= POT_CHANGE_THRESHOLD {
-            self.last_sent = normalized;
-            let msg = (self.map_fn)(normalized);
-            if let Err(e) = sender.try_send(msg) {
-                warn!("Pot message dropped (queue full): {:?}", e);
+        let normalized =
+            ((self.filtered - self.calibration.min) * self.range_reciprocal).clamp(0.0, 1.0);
+
+        // 5. Slew limit: move toward the reading by at most max_slew_per_poll
+        // The first poll jumps straight to the reading
+        let normalized = match self.slewed {
+            Some(prev) => {
+                prev + (normalized - prev).clamp(-self.max_slew_per_poll, self.max_slew_per_poll)
             }
+            None => normalized,
+        };
+        self.slewed = Some(normalized);
+
+        // 6. Deadband check: only send if changed significantly
+        let changed = self
+            .last_sent
+            .is_none_or(|last| (normalized - last).abs() >= POT_CHANGE_THRESHOLD);
+        if changed {
+            self.last_sent = Some(normalized);
+            self.pending = Some((self.map_fn)(normalized));
+        }
+
+        // 7. Send the latest value if there's room (retried on every poll)
+        self.flush(sender).await;
+    }
+
+    /// Send the pending message unless the queue is nearly full.
+    ///
+    /// Pot values are continuous, so when the queue is busy the message stays
+    /// pending and is simply replaced by the next reading: a sweep coalesces
+    /// into its latest value instead of flooding the queue, the poll loop never
+    /// blocks, and the last MESSAGE_QUEUE_NEARLY_FULL slots stay available for
+    /// discrete messages.
+    async fn flush(&mut self, sender: CtrlSender) {
+        let Some(msg) = self.pending else {
+            return;
+        };
+        if send_prioritized(sender, msg).await {
+            self.pending = None;
         }
     }
 }
@@ -102,6 +216,19 @@ pub fn map_freq(normalized: f32) -> Message {
     Message::SetFrequency(freq)
 }
 
+/// Map normalized potentiometer value to filter cutoff (Hz), exponentially
+///
+/// Equal knob travel gives equal musical intervals across FILTER_CUTOFF_MIN..FILTER_CUTOFF_MAX.
+pub fn map_cutoff(normalized: f32) -> Message {
+    let cutoff = FILTER_CUTOFF_MIN * (FILTER_CUTOFF_MAX / FILTER_CUTOFF_MIN).powf(normalized);
+    Message::SetCutoff(cutoff)
+}
+
+/// Map normalized potentiometer value to filter resonance (0.0 to 1.0)
+pub fn map_resonance(normalized: f32) -> Message {
+    Message::SetResonance(normalized)
+}
+
 /// Map normalized potentiometer value to volume using dB calculation
 pub fn map_vol(normalized: f32) -> Message {
     #[allow(non_snake_case)]
@@ -1,11 +1,29 @@
 //! Control tasks: potentiometers, buttons, and future encoders.
 
 use crate::config::*;
-use crate::controls::{map_freq, map_vol, CtrlSender, Potentiometer};
+use crate::controls::{
+    map_cutoff, map_freq, map_resonance, map_vol, CalibrateSignal, CtrlSender, Potentiometer,
+    RebindSignal,
+};
 use crate::hardware::{AdcBus, PotPin};
+use crate::message::Message;
+use crate::preset::{CalibrationState, PresetRequest, PresetRequests};
 use embassy_time::{Duration, Timer};
+use esp_hal::analog::adc::AdcChannel;
+use log::info;
 
-/// Potentiometer polling task: sequentially reads all pots.
+/// Parameters the second pot cycles through on each rebind, starting with volume.
+const SECOND_POT_MAPPINGS: [fn(f32) -> Message; 3] = [map_vol, map_cutoff, map_resonance];
+
+/// Button signals handled by the pot task.
+pub struct PotButtons {
+    /// Moves the second pot to its next mapping
+    pub rebind: &'static RebindSignal,
+    /// Starts learning both pot ranges; the next press adopts and saves them
+    pub calibrate: &'static CalibrateSignal,
+}
+
+/// Potentiometer polling loop: sequentially reads all pots.
 ///
 /// Owns the ADC peripheral and all pot pins. Each pot has independent
 /// signal processing state (EMA filter, deadband) but shares the hardware.
@@ -15,29 +33,81 @@ use embassy_time::{Duration, Timer};
 /// - Potentiometers are slow-changing (15ms poll rate is plenty)
 /// - ADC read takes ~1-2μs vs 15ms sleep → overhead is negligible
 ///
+/// The second pot starts on volume; every `rebind` signal moves it to the
+/// next entry of SECOND_POT_MAPPINGS (volume → cutoff → resonance → volume).
+///
+/// The first `calibrate` signal starts calibration: sweep both pots end to
+/// end, then signal again to adopt the learned ranges and queue them for the
+/// preset task to save, so they are restored at the next boot.
+///
+/// Generic over the pot pins, so the board layout is picked by the binary;
+/// embassy tasks can't be generic, so spawn it from a small task wrapper
+/// that names the concrete pins (see `main.rs`).
+///
 /// # Arguments
 /// * `sender` - Embassy channel sender for control messages
 /// * `adc_bus` - ADC bus with ADC peripheral (owned by this task)
-/// * `freq_pin` - Frequency potentiometer pin (GPIO1)
-/// * `vol_pin` - Volume potentiometer pin (GPIO2)
-#[embassy_executor::task]
-pub async fn pot_task(
+/// * `freq_pin` - Frequency potentiometer pin
+/// * `vol_pin` - Second potentiometer pin
+/// * `buttons` - Rebind and calibrate signals
+/// * `calibration` - Pot ranges to start with (stored at the last calibration)
+/// * `presets` - Preset task queue that saves new calibrations
+pub async fn poll_pots<PF, PV>(
     sender: CtrlSender,
     mut adc_bus: AdcBus,
-    mut freq_pin: PotPin<esp_hal::peripherals::GPIO1<'static>>,
-    mut vol_pin: PotPin<esp_hal::peripherals::GPIO2<'static>>,
-) {
-    // Create pot state objects with mapping functions and deadbands
-    let mut freq_pot = Potentiometer::new(map_freq);
-    let mut vol_pot = Potentiometer::new(map_vol);
+    mut freq_pin: PotPin<PF>,
+    mut vol_pin: PotPin<PV>,
+    buttons: PotButtons,
+    calibration: CalibrationState,
+    presets: &'static PresetRequests,
+) -> !
+where
+    PF: AdcChannel,
+    PV: AdcChannel,
+{
+    // Create pot state objects with mapping functions and slew limits
+    let mut freq_pot = Potentiometer::new(map_freq, POT_FREQ_MAX_SLEW_PER_POLL);
+    let mut vol_pot = Potentiometer::new(SECOND_POT_MAPPINGS[0], POT_NO_SLEW_LIMIT);
+    let mut vol_mapping = 0;
+    let [freq_calibration, vol_calibration] = calibration.pots;
+    freq_pot.set_calibration(freq_calibration);
+    vol_pot.set_calibration(vol_calibration);
+    let mut calibrating = false;
 
     loop {
-        // Poll frequency pot (GPIO1)
+        // Rebind the second pot if the mode button was pressed since the last poll
+        if buttons.rebind.try_take().is_some() {
+            vol_mapping = (vol_mapping + 1) % SECOND_POT_MAPPINGS.len();
+            vol_pot.set_map_fn(SECOND_POT_MAPPINGS[vol_mapping]);
+            info!("Second pot mapping {}", vol_mapping);
+        }
+
+        // Calibrate button toggles learning; finishing persists whatever was adopted
+        if buttons.calibrate.try_take().is_some() {
+            if calibrating {
+                let freq_learned = freq_pot.finish_calibration();
+                let vol_learned = vol_pot.finish_calibration();
+                if freq_learned || vol_learned {
+                    let pots = [freq_pot.calibration(), vol_pot.calibration()];
+                    presets
+                        .send(PresetRequest::SaveCalibration(CalibrationState { pots }))
+                        .await;
+                    info!("Pot calibration saved");
+                }
+            } else {
+                freq_pot.start_calibration();
+                vol_pot.start_calibration();
+                info!("Pot calibration: sweep both pots end to end, then press again");
+            }
+            calibrating = !calibrating;
+        }
+
+        // Poll frequency pot
         freq_pot
             .poll_and_send(sender, &mut adc_bus.adc, &mut freq_pin)
             .await;
 
-        // Poll volume pot (GPIO2)
+        // Poll second pot
         vol_pot
             .poll_and_send(sender, &mut adc_bus.adc, &mut vol_pin)
             .await;
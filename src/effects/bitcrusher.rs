@@ -0,0 +1,79 @@
+//! Bitcrusher: amplitude quantization and sample-rate reduction.
+
+use crate::config::{BITCRUSHER_MAX_BITS, BITCRUSHER_MAX_DOWNSAMPLE};
+use micromath::F32Ext;
+
+/// Bit-depth and sample-rate reduction effect.
+///
+/// Bit reduction rounds the f32 sample to 2^bits levels across -1.0..1.0;
+/// sample-rate reduction holds every Nth sample for N samples.
+pub struct Bitcrusher {
+    /// Target bit depth (BITCRUSHER_MAX_BITS = no quantization)
+    bits: u8,
+    /// Quantization steps per unit amplitude (2^(bits - 1))
+    levels: f32,
+    /// Reciprocal of `levels` (avoids a division per sample)
+    levels_reciprocal: f32,
+    /// Hold length in samples (1 = no downsampling)
+    downsample: u8,
+    /// Samples remaining before the next input is captured
+    hold_counter: u8,
+    /// Currently held output sample
+    held: f32,
+}
+
+impl Bitcrusher {
+    /// Create a bitcrusher at its neutral setting (full bit depth, no downsampling).
+    pub fn new() -> Self {
+        let levels = (1u32 << (BITCRUSHER_MAX_BITS - 1)) as f32;
+        Self {
+            bits: BITCRUSHER_MAX_BITS,
+            levels,
+            levels_reciprocal: 1.0 / levels,
+            downsample: 1,
+            hold_counter: 0,
+            held: 0.0,
+        }
+    }
+
+    /// Set bit depth (clamped to 1..=BITCRUSHER_MAX_BITS, max = bypass).
+    pub fn set_bits(&mut self, bits: u8) {
+        self.bits = bits.clamp(1, BITCRUSHER_MAX_BITS);
+        self.levels = (1u32 << (self.bits - 1)) as f32;
+        self.levels_reciprocal = 1.0 / self.levels;
+    }
+
+    /// Set sample-hold length (clamped to 1..=BITCRUSHER_MAX_DOWNSAMPLE, 1 = bypass).
+    pub fn set_downsample(&mut self, factor: u8) {
+        self.downsample = factor.clamp(1, BITCRUSHER_MAX_DOWNSAMPLE);
+        self.hold_counter = self.hold_counter.min(self.downsample - 1);
+    }
+
+    /// Process one sample.
+    ///
+    /// At full bit depth with no downsampling the input is returned unchanged.
+    #[inline(always)]
+    pub fn process(&mut self, x: f32) -> f32 {
+        let quantize = self.bits < BITCRUSHER_MAX_BITS;
+        if !quantize && self.downsample == 1 {
+            return x;
+        }
+
+        if self.hold_counter == 0 {
+            self.held = if quantize {
+                (x * self.levels).round() * self.levels_reciprocal
+            } else {
+                x
+            };
+            self.hold_counter = self.downsample;
+        }
+        self.hold_counter -= 1;
+        self.held
+    }
+}
+
+impl Default for Bitcrusher {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,76 @@
+//! DC blocker: one-pole high-pass that removes constant offset from the mix.
+
+use crate::config::DC_BLOCKER_CUTOFF_HZ;
+
+/// One-pole DC-blocking filter, `y[n] = x[n] - x[n-1] + R * y[n-1]`.
+///
+/// R is derived from DC_BLOCKER_CUTOFF_HZ, so the corner stays a few Hz
+/// (inaudible) at any sample rate while a constant offset decays with a
+/// time constant of 1 / (2π · cutoff) seconds.
+pub struct DcBlocker {
+    /// Pole radius (just below 1.0)
+    r: f32,
+    /// Previous input sample
+    x_prev: f32,
+    /// Previous output sample
+    y_prev: f32,
+}
+
+impl DcBlocker {
+    /// Create a DC blocker with its corner at DC_BLOCKER_CUTOFF_HZ.
+    ///
+    /// # Arguments
+    /// * `sample_rate` - Audio sample rate in Hz
+    pub fn new(sample_rate: f32) -> Self {
+        let r = 1.0 - core::f32::consts::TAU * DC_BLOCKER_CUTOFF_HZ / sample_rate;
+        Self {
+            r: r.clamp(0.0, 1.0),
+            x_prev: 0.0,
+            y_prev: 0.0,
+        }
+    }
+
+    /// Process one sample.
+    #[inline(always)]
+    pub fn process(&mut self, x: f32) -> f32 {
+        let y = x - self.x_prev + self.r * self.y_prev;
+        self.x_prev = x;
+        self.y_prev = y;
+        y
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::SAMPLE_RATE;
+
+    /// 0.5 DC offset under a 440 Hz tone at 0.25 amplitude.
+    fn biased_tone(n: usize) -> f32 {
+        let t = n as f32 / SAMPLE_RATE as f32;
+        0.5 + 0.25 * (core::f32::consts::TAU * 440.0 * t).sin()
+    }
+
+    #[test]
+    fn dc_offset_settles_to_zero() {
+        let mut blocker = DcBlocker::new(SAMPLE_RATE as f32);
+        let half_second = SAMPLE_RATE as usize / 2;
+        let tail = SAMPLE_RATE as usize / 10;
+
+        let mut sum = 0.0;
+        let mut energy = 0.0;
+        for n in 0..half_second {
+            let y = blocker.process(biased_tone(n));
+            if n >= half_second - tail {
+                sum += y;
+                energy += y * y;
+            }
+        }
+
+        // Offset gone within half a second, tone untouched (RMS of 0.25 sine ≈ 0.177)
+        let mean = sum / tail as f32;
+        let rms = (energy / tail as f32).sqrt();
+        assert!(mean.abs() < 1e-3, "mean {mean}");
+        assert!((rms - 0.25 * core::f32::consts::FRAC_1_SQRT_2).abs() < 2e-3, "rms {rms}");
+    }
+}
@@ -0,0 +1,122 @@
+//! Limiter: feed-forward gain reduction that keeps the voice mix below a threshold.
+
+use crate::config::{LIMITER_ATTACK_MS, LIMITER_RELEASE_MS, LIMITER_THRESHOLD};
+use micromath::F32Ext;
+
+/// Look-ahead-free peak limiter.
+///
+/// The gain needed to bring |x| down to LIMITER_THRESHOLD is the target;
+/// the applied gain glides toward it with the attack coefficient when
+/// backing off and the release coefficient when recovering, so gain
+/// reduction is smooth rather than stepwise. Below the threshold the
+/// target is exactly 1.0 (quiet passages are never attenuated).
+pub struct Limiter {
+    /// Whether gain reduction is applied (false = bypass)
+    enabled: bool,
+    /// Smoothed gain (1.0 = no reduction)
+    gain: f32,
+    /// Per-sample smoothing coefficient while gain is falling
+    attack_coeff: f32,
+    /// Per-sample smoothing coefficient while gain is recovering
+    release_coeff: f32,
+}
+
+impl Limiter {
+    /// Create an enabled limiter with attack/release times from config.
+    ///
+    /// # Arguments
+    /// * `sample_rate` - Audio sample rate in Hz
+    pub fn new(sample_rate: f32) -> Self {
+        Self {
+            enabled: true,
+            gain: 1.0,
+            attack_coeff: smoothing_coeff(LIMITER_ATTACK_MS, sample_rate),
+            release_coeff: smoothing_coeff(LIMITER_RELEASE_MS, sample_rate),
+        }
+    }
+
+    /// Enable or bypass gain reduction (bypass resets the gain to unity).
+    pub fn set_enabled(&mut self, enabled: bool) {
+        self.enabled = enabled;
+        if !enabled {
+            self.gain = 1.0;
+        }
+    }
+
+    /// Process one sample.
+    #[inline(always)]
+    pub fn process(&mut self, x: f32) -> f32 {
+        if !self.enabled {
+            return x;
+        }
+
+        let magnitude = x.abs();
+        let target = if magnitude > LIMITER_THRESHOLD {
+            LIMITER_THRESHOLD / magnitude
+        } else {
+            1.0
+        };
+
+        let coeff = if target < self.gain {
+            self.attack_coeff
+        } else {
+            self.release_coeff
+        };
+        self.gain = target + (self.gain - target) * coeff;
+        x * self.gain
+    }
+}
+
+/// One-pole coefficient that covers ~63% of a step in `time_ms`.
+fn smoothing_coeff(time_ms: f32, sample_rate: f32) -> f32 {
+    let samples = time_ms * 0.001 * sample_rate;
+    if samples <= 1.0 {
+        0.0
+    } else {
+        (-1.0 / samples).exp()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE_RATE: f32 = 48_000.0;
+
+    #[test]
+    fn unity_gain_below_the_threshold() {
+        let mut limiter = Limiter::new(SAMPLE_RATE);
+        for n in 0..SAMPLE_RATE as usize {
+            let x = LIMITER_THRESHOLD * (n as f32 * 0.01).sin();
+            assert_eq!(limiter.process(x), x);
+        }
+        assert_eq!(limiter.gain, 1.0);
+    }
+
+    #[test]
+    fn gain_reduction_glides_instead_of_stepping() {
+        let mut limiter = Limiter::new(SAMPLE_RATE);
+        let loud = 2.0 * LIMITER_THRESHOLD;
+        let largest_fall = (1.0 - limiter.attack_coeff) * 0.5;
+
+        // Attack: the gain backs off by at most one smoothing step per sample
+        let mut previous = limiter.gain;
+        for _ in 0..(10.0 * LIMITER_ATTACK_MS * 0.001 * SAMPLE_RATE) as usize {
+            limiter.process(loud);
+            assert!(limiter.gain <= previous);
+            assert!(previous - limiter.gain <= largest_fall + 1e-6);
+            previous = limiter.gain;
+        }
+        let limited = limiter.process(loud);
+        assert!((limited - LIMITER_THRESHOLD).abs() < 1e-3, "settled at {limited}");
+
+        // Release: back below the threshold the gain climbs back gradually
+        let quiet = 0.5 * LIMITER_THRESHOLD;
+        let first = limiter.process(quiet) / quiet;
+        assert!(first < 0.51, "gain jumped to {first}");
+        for _ in 0..(10.0 * LIMITER_RELEASE_MS * 0.001 * SAMPLE_RATE) as usize {
+            limiter.process(quiet);
+        }
+        assert!(limiter.gain > 0.999);
+    }
+}
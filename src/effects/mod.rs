@@ -0,0 +1,20 @@
+//! Master-bus effects applied to the mixed voice signal.
+//!
+//! Each effect is a small struct with `process(&mut self, x: f32) -> f32`,
+//! run per sample by the engine after voice mixing (`StereoWidth` works on
+//! the final L/R pair instead). Every user-controlled
+//! effect has a neutral setting at which it passes the signal through
+//! untouched; the DC blocker always runs, last in the chain.
+
+pub mod bitcrusher;
+pub mod dc_blocker;
+pub mod limiter;
+pub mod stereo_width;
+pub mod tremolo;
+
+// Re-export commonly used items
+pub use bitcrusher::Bitcrusher;
+pub use dc_blocker::DcBlocker;
+pub use limiter::Limiter;
+pub use stereo_width::StereoWidth;
+pub use tremolo::Tremolo;
@@ -0,0 +1,77 @@
+//! Stereo width: mid/side scaling of the final L/R pair.
+
+use crate::config::STEREO_WIDTH_MAX;
+
+/// Mid/side width control.
+///
+/// mid = (L + R) / 2, side = (L - R) / 2; side is scaled by the width and
+/// the pair recombined. 0.0 collapses to mono (the average of L and R, so it
+/// never exceeds the louder channel), 1.0 passes the pair through untouched,
+/// above 1.0 widens.
+///
+/// The engine mix is still mono, so `render` currently feeds L == R: the side
+/// signal is zero and the stage has no audible effect until voices are panned.
+pub struct StereoWidth {
+    /// Side gain (0.0 to STEREO_WIDTH_MAX, 1.0 = bypass)
+    width: f32,
+}
+
+impl StereoWidth {
+    /// Create a width control at 1.0 (bypassed).
+    pub fn new() -> Self {
+        Self { width: 1.0 }
+    }
+
+    /// Set width (clamped to 0.0..=STEREO_WIDTH_MAX).
+    pub fn set_width(&mut self, width: f32) {
+        self.width = width.clamp(0.0, STEREO_WIDTH_MAX);
+    }
+
+    /// Process one L/R pair.
+    ///
+    /// At width 1.0 the input is returned unchanged (bit-identical).
+    #[inline(always)]
+    pub fn process(&self, left: f32, right: f32) -> (f32, f32) {
+        if self.width == 1.0 {
+            return (left, right);
+        }
+
+        let mid = (left + right) * 0.5;
+        let side = (left - right) * 0.5 * self.width;
+        (mid + side, mid - side)
+    }
+}
+
+impl Default for StereoWidth {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Uncorrelated L/R pairs covering both signs and unequal levels.
+    const PAIRS: [(f32, f32); 5] =
+        [(0.3, -0.7), (1.0, 0.0), (-0.25, -0.5), (0.1, 0.1), (0.9, -0.9)];
+
+    #[test]
+    fn unit_width_passes_the_pair_through() {
+        let width = StereoWidth::new();
+        for (left, right) in PAIRS {
+            let (l, r) = width.process(left, right);
+            assert_eq!((l.to_bits(), r.to_bits()), (left.to_bits(), right.to_bits()));
+        }
+    }
+
+    #[test]
+    fn zero_width_collapses_to_the_average() {
+        let mut width = StereoWidth::new();
+        width.set_width(0.0);
+        for (left, right) in PAIRS {
+            let mid = (left + right) / 2.0;
+            assert_eq!(width.process(left, right), (mid, mid));
+        }
+    }
+}
@@ -0,0 +1,52 @@
+//! Tremolo: amplitude modulation by an internal sine LFO.
+
+use crate::config::{TREMOLO_DEFAULT_RATE_HZ, TREMOLO_RATE_MAX, TREMOLO_RATE_MIN};
+use crate::oscillator::Oscillator;
+
+/// Amplitude modulation effect.
+///
+/// Gain swings between 1.0 and (1.0 - depth) once per LFO cycle, so the
+/// signal is only ever attenuated, never boosted.
+pub struct Tremolo {
+    /// Sine LFO driving the gain
+    lfo: Oscillator,
+    /// Modulation depth (0.0 = bypass, 1.0 = full gating)
+    depth: f32,
+}
+
+impl Tremolo {
+    /// Create a tremolo at TREMOLO_DEFAULT_RATE_HZ with zero depth (bypassed).
+    ///
+    /// # Arguments
+    /// * `sample_rate` - Audio sample rate in Hz
+    pub fn new(sample_rate: f32) -> Self {
+        Self {
+            lfo: Oscillator::new(TREMOLO_DEFAULT_RATE_HZ, sample_rate),
+            depth: 0.0,
+        }
+    }
+
+    /// Set LFO rate in Hz (clamped to TREMOLO_RATE_MIN..=TREMOLO_RATE_MAX).
+    pub fn set_rate(&mut self, rate: f32) {
+        self.lfo.set_frequency(rate.clamp(TREMOLO_RATE_MIN, TREMOLO_RATE_MAX));
+    }
+
+    /// Set modulation depth (0.0 to 1.0).
+    pub fn set_depth(&mut self, depth: f32) {
+        self.depth = depth.clamp(0.0, 1.0);
+    }
+
+    /// Process one sample.
+    ///
+    /// At depth 0.0 the input is returned unchanged (LFO is not advanced).
+    #[inline(always)]
+    pub fn process(&mut self, x: f32) -> f32 {
+        if self.depth == 0.0 {
+            return x;
+        }
+
+        // Map LFO -1..1 to 0..1, then scale attenuation by depth
+        let lfo_unipolar = 0.5 + 0.5 * self.lfo.tick();
+        x * (1.0 - self.depth * lfo_unipolar)
+    }
+}
@@ -1,55 +1,435 @@
 //! Engine module: manages voices, processes messages, renders audio.
 
 use core::array::from_fn;
+use core::cell::RefCell;
 
-use crate::config::{MESSAGE_QUEUE_SIZE, VOICE_COUNT, STARTING_FREQUENCY, MASTER_GAIN};
-use crate::message::Message;
+use crate::arpeggiator::{pitch_order, Arpeggiator};
+use crate::audio_util::{f32_to_i16_le, f32_to_i24_le, OutputFormat, MAX_FRAME_SIZE};
+use crate::config::{
+    DMA_BUFFER_SIZE, FADE_MS, MASTER_GAIN, MESSAGE_QUEUE_SIZE, PITCH_BEND_RANGE_SEMITONES,
+    RENDER_BLOCK_SIZE, STARTING_FREQUENCY, USER_WAVETABLE_SLOTS, VOICE_COUNT,
+    VOLUME_SMOOTHING_COEFF, WAVETABLE_SIZE,
+};
+use crate::effects::{Bitcrusher, DcBlocker, Limiter, StereoWidth, Tremolo};
+use crate::message::{Message, MessageSource};
+use crate::meter::VoiceLevels;
+use crate::oscillator::Wavetable;
+use crate::pitch::{note_to_frequency, semitone_ratio};
+use crate::preset::{
+    EngineState, FlashMute, LoadedPreset, PresetRequest, PresetRequests, VoiceState,
+};
 use crate::voice::Voice;
 use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+use embassy_sync::blocking_mutex::Mutex;
 use embassy_sync::channel::Receiver;
+use log::warn;
+
+/// User wavetable slots shared by all voices.
+///
+/// 4 KB per slot, so the storage lives in a `StaticCell` rather than in the
+/// engine (which sits inside the main task's future).
+pub type UserWavetables = [Wavetable; USER_WAVETABLE_SLOTS];
+
+/// Hand-off table for loading user wavetables at runtime.
+///
+/// A control task writes the samples here and then sends
+/// `Message::LoadWavetable(slot)`; the engine copies them into the slot when
+/// it processes the message. Each side holds the lock for a single 4 KB copy,
+/// so the audio task never waits on the writer for long.
+pub struct WavetableStaging {
+    table: Mutex<CriticalSectionRawMutex, RefCell<Wavetable>>,
+}
+
+impl WavetableStaging {
+    /// Staging table filled with silence.
+    pub const fn new() -> Self {
+        Self {
+            table: Mutex::new(RefCell::new([0.0; WAVETABLE_SIZE])),
+        }
+    }
+
+    /// Replace the staged samples.
+    pub fn write(&self, table: &Wavetable) {
+        self.table.lock(|staged| staged.borrow_mut().copy_from_slice(table));
+    }
+
+    /// Copy the staged samples into `dest`.
+    fn read_into(&self, dest: &mut Wavetable) {
+        self.table.lock(|staged| *dest = *staged.borrow());
+    }
+}
+
+impl Default for WavetableStaging {
+    fn default() -> Self {
+        Self::new()
+    }
+}
 
 /// Main synth engine managing all voices.
-pub struct Engine {
+///
+/// Generic over where control messages come from; on the device this is
+/// the embassy channel receiver (the default), on a host a `QueueSource`.
+pub struct Engine<S = Receiver<'static, CriticalSectionRawMutex, Message, MESSAGE_QUEUE_SIZE>> {
     /// Array of voices (size determined by VOICE_COUNT config)
     voices: [Voice; VOICE_COUNT],
 
     /// Currently selected voice for control (None = no selection)
+    /// Pots/encoders target it; NoteOn/NoteOff go through the allocator instead
     selected_voice: Option<u8>,
 
+    /// Allocation order stamp of the last note started on each voice (higher = newer)
+    note_stamps: [u32; VOICE_COUNT],
+
+    /// Stamp given to the next NoteOn
+    next_note_stamp: u32,
+
     /// Audio sample rate (stored for future use in filters/effects)
     #[allow(dead_code)]
     sample_rate: f32,
 
-    /// Message receiver from control tasks
-    receiver: Receiver<'static, CriticalSectionRawMutex, Message, MESSAGE_QUEUE_SIZE>,
+    /// PCM layout written by render() (must match the I2S data format)
+    format: OutputFormat,
+
+    /// Frame split across render() calls: bytes still owed to the next buffer
+    carry: [u8; MAX_FRAME_SIZE],
+
+    /// Start of the owed bytes within `carry`
+    carry_start: usize,
+
+    /// Number of owed bytes (0 = frame boundary, nothing pending)
+    carry_len: usize,
+
+    /// Message source fed by control tasks
+    receiver: S,
+
+    /// Sum of the voices' fade gains the mix is currently normalized by
+    mix_weight: f32,
+
+    /// Cached 1 / max(mix_weight, 1.0) (for fast normalization)
+    mix_reciprocal: f32,
+
+    /// Steps through active voices one at a time when enabled
+    arpeggiator: Arpeggiator,
+
+    /// Voice currently let through by the arpeggiator
+    arp_voice: Option<u8>,
+
+    /// Hard-sync master voice (None = no sync)
+    sync_master: Option<u8>,
+
+    /// Voice whose phase is reset each time the sync master completes a cycle
+    sync_slave: u8,
+
+    /// Live master level set by SetMasterVolume (0.0 to 1.0, applied with MASTER_GAIN)
+    master_volume_target: f32,
+
+    /// Smoothed master level, stepped toward the target every sample
+    master_volume_current: f32,
+
+    /// Headroom limiter on the normalized mix (before master gain)
+    limiter: Limiter,
+
+    /// Master tremolo (after mixing)
+    tremolo: Tremolo,
+
+    /// Master bitcrusher (after tremolo)
+    bitcrusher: Bitcrusher,
+
+    /// Removes DC offset from the mix (last mono stage)
+    dc_blocker: DcBlocker,
+
+    /// Mid/side width on the L/R pair written by render()
+    stereo_width: StereoWidth,
+
+    /// Queue to the preset task (None = SavePreset/LoadPreset are ignored)
+    preset_requests: Option<&'static PresetRequests>,
+
+    /// Snapshots read back by the preset task, restored on the next render
+    loaded_preset: Option<&'static LoadedPreset>,
+
+    /// Handshake silencing the output around flash writes (None = never muted)
+    flash_mute: Option<&'static FlashMute>,
+
+    /// Whether a flash write has asked for silence (latched once per render)
+    muting: bool,
+
+    /// Flash-write mute gain, ramped toward 0.0 while muting and 1.0 otherwise
+    mute_gain: f32,
+
+    /// Per-sample mute ramp increment (FADE_MS for a full ramp)
+    mute_step: f32,
+
+    /// Bytes rendered at zero mute gain in a row, up to one DMA ring
+    silent_bytes: usize,
 
-    /// Number of currently active voices
-    active_count: u32,
+    /// User wavetable storage (None = `Waveform::Custom` plays the built-in sine)
+    user_wavetables: Option<&'static mut UserWavetables>,
 
-    /// Cached reciprocal of active voice count (for fast normalization)
-    active_count_reciprocal: f32,
+    /// Tables staged by a control task for LoadWavetable (None = LoadWavetable is ignored)
+    wavetable_staging: Option<&'static WavetableStaging>,
+
+    /// Per-voice output levels published after every render (None = not metered)
+    level_meter: Option<&'static VoiceLevels>,
 }
 
-impl Engine {
+impl<S: MessageSource> Engine<S> {
     /// Create new engine with initialized voices and message receiver.
     ///
     /// # Arguments
     /// * `sample_rate` - Audio sample rate in Hz
-    /// * `receiver` - Embassy channel receiver for control messages
+    /// * `format` - Output PCM layout (must match the I2S data format)
+    /// * `receiver` - Source of control messages (embassy channel receiver on device)
     ///
     /// # Returns
     /// Engine with VOICE_COUNT voices at STARTING_FREQUENCY, inactive, no selection
-    pub fn new(
-        sample_rate: f32,
-        receiver: Receiver<'static, CriticalSectionRawMutex, Message, MESSAGE_QUEUE_SIZE>,
-    ) -> Self {
+    pub fn new(sample_rate: f32, format: OutputFormat, receiver: S) -> Self {
         Self {
-            voices: from_fn(|_| Voice::new(STARTING_FREQUENCY, sample_rate)),
+            voices: from_fn(|i| Voice::new(STARTING_FREQUENCY, sample_rate, noise_seed(i))),
             selected_voice: None,
+            note_stamps: [0; VOICE_COUNT],
+            next_note_stamp: 1,
             sample_rate,
+            format,
+            carry: [0; MAX_FRAME_SIZE],
+            carry_start: 0,
+            carry_len: 0,
             receiver,
-            active_count: 0,
-            active_count_reciprocal: 1.0,
+            mix_weight: 0.0,
+            mix_reciprocal: 1.0,
+            arpeggiator: Arpeggiator::new(sample_rate),
+            arp_voice: None,
+            sync_master: None,
+            sync_slave: 0,
+            master_volume_target: 1.0,
+            master_volume_current: 1.0,
+            limiter: Limiter::new(sample_rate),
+            tremolo: Tremolo::new(sample_rate),
+            bitcrusher: Bitcrusher::new(),
+            dc_blocker: DcBlocker::new(sample_rate),
+            stereo_width: StereoWidth::new(),
+            preset_requests: None,
+            loaded_preset: None,
+            flash_mute: None,
+            muting: false,
+            mute_gain: 1.0,
+            mute_step: 1000.0 / (FADE_MS * sample_rate),
+            silent_bytes: 0,
+            user_wavetables: None,
+            wavetable_staging: None,
+            level_meter: None,
+        }
+    }
+
+    /// Connect SavePreset/LoadPreset messages to the preset task (see `preset_task`).
+    ///
+    /// # Arguments
+    /// * `requests` - Queue the preset task serves
+    /// * `loaded` - Signal the preset task raises with each snapshot it reads
+    /// * `mute` - Handshake the preset task uses to silence the output around writes
+    pub fn set_preset_channels(
+        &mut self,
+        requests: &'static PresetRequests,
+        loaded: &'static LoadedPreset,
+        mute: &'static FlashMute,
+    ) {
+        self.preset_requests = Some(requests);
+        self.loaded_preset = Some(loaded);
+        self.flash_mute = Some(mute);
+    }
+
+    /// Attach storage for user wavetables selected via `Waveform::Custom(slot)`.
+    pub fn set_user_wavetables(&mut self, tables: &'static mut UserWavetables) {
+        self.user_wavetables = Some(tables);
+    }
+
+    /// Accept `Message::LoadWavetable` requests, copying tables from `staging`.
+    pub fn set_wavetable_staging(&mut self, staging: &'static WavetableStaging) {
+        self.wavetable_staging = Some(staging);
+    }
+
+    /// Publish each voice's peak level to `levels` after every render (for the voice LEDs).
+    pub fn set_level_meter(&mut self, levels: &'static VoiceLevels) {
+        self.level_meter = Some(levels);
+    }
+
+    /// Copy the staged table into `slot` (see `Message::LoadWavetable`).
+    fn load_staged_wavetable(&mut self, slot: u8) {
+        let Some(staging) = self.wavetable_staging else {
+            warn!("LoadWavetable ignored: no staging table attached");
+            return;
+        };
+        if let Some(dest) = self.user_wavetable_mut(slot) {
+            staging.read_into(dest);
+        }
+    }
+
+    /// User wavetable storage for `slot` (None, with a warning, if unavailable).
+    fn user_wavetable_mut(&mut self, slot: u8) -> Option<&mut Wavetable> {
+        let dest = self
+            .user_wavetables
+            .as_deref_mut()
+            .and_then(|tables| tables.get_mut(slot as usize));
+        if dest.is_none() {
+            warn!("Wavetable slot {} unavailable", slot);
+        }
+        dest
+    }
+
+    /// Capture the user-facing engine state for saving as a preset.
+    pub fn snapshot(&self) -> EngineState {
+        EngineState {
+            voices: from_fn(|i| {
+                let voice = &self.voices[i];
+                VoiceState {
+                    frequency: voice.frequency(),
+                    volume: voice.volume(),
+                    waveform: voice.waveform(),
+                    pulse_width: voice.pulse_width(),
+                    sub_level: voice.sub_level(),
+                    sub_waveform: voice.sub_waveform(),
+                    cutoff: voice.cutoff(),
+                    resonance: voice.resonance(),
+                    key_track: voice.key_track(),
+                    retrigger: voice.retrigger(),
+                    active: voice.active,
+                }
+            }),
+            selected_voice: self.selected_voice,
+        }
+    }
+
+    /// Apply a previously captured state.
+    ///
+    /// Volume and sub-level changes glide through the usual smoothing, and cached
+    /// state (mix weight, normalization reciprocal) is re-derived from the voices.
+    pub fn restore(&mut self, state: &EngineState) {
+        for (voice, saved) in self.voices.iter_mut().zip(&state.voices) {
+            voice.set_frequency(saved.frequency);
+            voice.set_volume(saved.volume);
+            voice.set_waveform(saved.waveform);
+            voice.set_pulse_width(saved.pulse_width);
+            voice.set_sub_level(saved.sub_level);
+            voice.set_sub_waveform(saved.sub_waveform);
+            voice.set_cutoff(saved.cutoff);
+            voice.set_resonance(saved.resonance);
+            voice.set_key_track(saved.key_track);
+            voice.set_retrigger(saved.retrigger);
+            voice.set_active(saved.active);
+        }
+        self.selected_voice = state.selected_voice.filter(|&idx| (idx as usize) < VOICE_COUNT);
+        self.update_mix();
+    }
+
+    /// Recompute the normalization from the fade gains and refresh arpeggiator gates.
+    fn update_mix(&mut self) {
+        self.set_mix_weight(self.fade_sum());
+        self.apply_arp_gates();
+    }
+
+    /// Cache the normalization reciprocal for a mix weight.
+    fn set_mix_weight(&mut self, weight: f32) {
+        self.mix_weight = weight;
+
+        // Cache reciprocal for fast multiplication (avoid division per sample)
+        // Below 1.0 only one voice is fading in or out, so it passes unscaled and its
+        // own ramp stays audible. Arpeggiator gates fade too, so with one voice let
+        // through the weight glides to 1.0 and no special case is needed
+        self.mix_reciprocal = 1.0 / weight.max(1.0);
+    }
+
+    /// Voice targeted by pots/encoders (None if nothing is selected or the index is stale).
+    fn selected_voice_mut(&mut self) -> Option<&mut Voice> {
+        self.voices.get_mut(self.selected_voice? as usize)
+    }
+
+    /// Pick the voice that plays a new note.
+    ///
+    /// In order of preference: the voice already holding `note` (re-struck),
+    /// a fully silent voice, a switched-off voice still ringing out its release,
+    /// and finally a busy voice is stolen (it fades out before the new note
+    /// starts, see `Voice::note_on`). Within each group the oldest note
+    /// goes first and ties go to the lowest index, so allocation is fully deterministic.
+    fn allocate_voice(&self, note: u8) -> usize {
+        if let Some(idx) = self.voices.iter().position(|v| v.note() == Some(note)) {
+            return idx;
+        }
+
+        // (rank, age) ordering: silent beats releasing beats busy, then oldest first
+        (0..VOICE_COUNT)
+            .min_by_key(|&idx| {
+                let voice = &self.voices[idx];
+                let rank = match (voice.active, voice.is_sounding()) {
+                    (false, false) => 0,
+                    (false, true) => 1,
+                    (true, _) => 2,
+                };
+                (rank, self.note_stamps[idx])
+            })
+            .unwrap_or(0)
+    }
+
+    /// Configure hard sync (see `Message::SetSync`); invalid routings leave sync unchanged.
+    fn set_sync(&mut self, master: Option<u8>, slave: u8) {
+        let Some(master) = master else {
+            self.sync_master = None;
+            return;
+        };
+        if master == slave {
+            warn!("Sync rejected: voice {} can't sync to itself", master);
+            return;
+        }
+        if master as usize >= VOICE_COUNT || slave as usize >= VOICE_COUNT {
+            warn!("Sync rejected: voice out of range ({} -> {})", master, slave);
+            return;
+        }
+        self.sync_master = Some(master);
+        self.sync_slave = slave;
+    }
+
+    /// Sum of the voices' fade gains (a fully faded-in voice counts 1.0).
+    fn fade_sum(&self) -> f32 {
+        self.voices.iter().map(Voice::fade).sum()
+    }
+
+    /// Follow the fade ramps, so the normalization glides as voices fade in and out.
+    #[inline(always)]
+    fn refresh_mix(&mut self) {
+        let weight = self.fade_sum();
+        if weight != self.mix_weight {
+            self.set_mix_weight(weight);
+        }
+    }
+
+    /// Advance the arpeggiator clock, moving to the next voice on a step boundary.
+    fn advance_arp(&mut self, frames: u32) {
+        if !self.arpeggiator.advance(frames) {
+            return;
+        }
+        self.step_arp();
+    }
+
+    /// Move the arpeggiator to the next active voice (by pitch) and gate the others.
+    fn step_arp(&mut self) {
+        let (order, len) = pitch_order(self.voices.iter().map(|v| (v.active, v.frequency())));
+        self.arp_voice = self.arpeggiator.next_voice(&order[..len]);
+        self.apply_arp_gates();
+    }
+
+    /// Open only the arpeggiated voice's gate (all gates when the arpeggiator is off).
+    fn apply_arp_gates(&mut self) {
+        let enabled = self.arpeggiator.enabled();
+        for (idx, voice) in self.voices.iter_mut().enumerate() {
+            voice.set_gate(!enabled || self.arp_voice == Some(idx as u8));
+        }
+    }
+
+    /// Hand a flash operation to the preset task without waiting for it.
+    fn request_preset(&self, request: PresetRequest) {
+        let Some(requests) = self.preset_requests else {
+            return;
+        };
+        if requests.try_send(request).is_err() {
+            warn!("Preset request dropped (preset task busy)");
         }
     }
 
@@ -77,89 +457,874 @@ impl Engine {
 
             Message::ToggleVoice(idx) => {
                 if let Some(voice) = self.voices.get_mut(idx as usize) {
-                    let was_active = voice.active;
-                    voice.set_active(!was_active);
-
-                    // Update active count and cache reciprocal
-                    if was_active {
-                        self.active_count = self.active_count.saturating_sub(1);
-                    } else {
-                        self.active_count += 1;
-                    }
-
-                    // Cache reciprocal for fast multiplication (avoid division in tick)
-                    self.active_count_reciprocal = if self.active_count > 0 {
-                        1.0 / self.active_count as f32
-                    } else {
-                        1.0 // Doesn't matter, sum will be 0.0
-                    };
+                    voice.toggle();
+                    self.update_mix();
                 }
             }
 
             Message::SetFrequency(freq) => {
-                if let Some(idx) = self.selected_voice {
-                    if let Some(voice) = self.voices.get_mut(idx as usize) {
-                        voice.set_frequency(freq);
-                    }
+                if let Some(voice) = self.selected_voice_mut() {
+                    voice.set_frequency(freq);
                 }
             }
 
             Message::SetVolume(vol) => {
-                if let Some(idx) = self.selected_voice {
-                    if let Some(voice) = self.voices.get_mut(idx as usize) {
-                        voice.set_volume(vol);
-                    }
+                if let Some(voice) = self.selected_voice_mut() {
+                    voice.set_volume(vol);
+                }
+            }
+
+            Message::SetWaveform(waveform) => {
+                if let Some(voice) = self.selected_voice_mut() {
+                    voice.set_waveform(waveform);
+                }
+            }
+
+            Message::SetPulseWidth(width) => {
+                if let Some(voice) = self.selected_voice_mut() {
+                    voice.set_pulse_width(width);
+                }
+            }
+
+            Message::SetSubLevel(level) => {
+                if let Some(voice) = self.selected_voice_mut() {
+                    voice.set_sub_level(level);
+                }
+            }
+
+            Message::SetSubWaveform(waveform) => {
+                if let Some(voice) = self.selected_voice_mut() {
+                    voice.set_sub_waveform(waveform);
+                }
+            }
+
+            Message::SetCutoff(cutoff) => {
+                if let Some(voice) = self.selected_voice_mut() {
+                    voice.set_cutoff(cutoff);
+                }
+            }
+
+            Message::SetResonance(resonance) => {
+                if let Some(voice) = self.selected_voice_mut() {
+                    voice.set_resonance(resonance);
+                }
+            }
+
+            Message::SetKeyTrack(amount) => {
+                if let Some(voice) = self.selected_voice_mut() {
+                    voice.set_key_track(amount);
+                }
+            }
+
+            Message::PitchBend(bend) => {
+                // Ratio is recomputed from the bend position, never accumulated
+                let ratio = semitone_ratio(bend.clamp(-1.0, 1.0) * PITCH_BEND_RANGE_SEMITONES);
+                for voice in self.voices.iter_mut() {
+                    voice.set_pitch_ratio(ratio);
+                }
+            }
+
+            Message::SetRetrigger(retrigger) => {
+                if let Some(voice) = self.selected_voice_mut() {
+                    voice.set_retrigger(retrigger);
+                }
+            }
+
+            Message::NoteOn { note, velocity: 0 } => self.process_message(Message::NoteOff { note }),
+
+            Message::NoteOn { note, velocity } => {
+                let idx = self.allocate_voice(note);
+                self.note_stamps[idx] = self.next_note_stamp;
+                self.next_note_stamp = self.next_note_stamp.wrapping_add(1);
+                self.voices[idx].note_on(note, note_to_frequency(note), velocity);
+                self.update_mix();
+            }
+
+            Message::NoteOff { note } => {
+                // After a steal the old note is held by no voice, so this is a no-op
+                let mut released = false;
+                for voice in self.voices.iter_mut() {
+                    released |= voice.note_off(note);
+                }
+                if released {
+                    self.update_mix();
+                }
+            }
+
+            Message::SetTremoloRate(rate) => self.tremolo.set_rate(rate),
+
+            Message::SetTremoloDepth(depth) => self.tremolo.set_depth(depth),
+
+            Message::SetBitDepth(bits) => self.bitcrusher.set_bits(bits),
+
+            Message::SetDownsample(factor) => self.bitcrusher.set_downsample(factor),
+
+            Message::SetArpEnabled(enabled) => {
+                self.arpeggiator.set_enabled(enabled);
+                self.update_mix();
+                if enabled {
+                    self.step_arp();
                 }
             }
+
+            Message::SetArpPattern(pattern) => self.arpeggiator.set_pattern(pattern),
+
+            Message::SetArpStepMs(step_ms) => self.arpeggiator.set_step_ms(step_ms),
+
+            Message::SetSync { master, slave } => self.set_sync(master, slave),
+
+            Message::SetMasterVolume(vol) => self.master_volume_target = vol.clamp(0.0, 1.0),
+
+            Message::SetWidth(width) => self.stereo_width.set_width(width),
+
+            Message::SetLimiter(enabled) => self.limiter.set_enabled(enabled),
+
+            Message::SavePreset(slot) => {
+                let state = self.snapshot();
+                self.request_preset(PresetRequest::Save { slot, state });
+            }
+
+            Message::LoadPreset(slot) => self.request_preset(PresetRequest::Load(slot)),
+
+            Message::LoadWavetable(slot) => self.load_staged_wavetable(slot),
         }
     }
 
     /// Generate next mixed audio sample from all voices.
     ///
     /// # Returns
-    /// Sum of all active voices, normalized by active count, with master gain applied
+    /// Sum of all sounding voices, normalized by the sum of their fade gains and
+    /// limited, with master gain, smoothed master volume and master effects applied
     pub fn tick(&mut self) -> f32 {
-        let sum: f32 = self.voices.iter_mut().map(|v| v.tick()).sum();
+        self.advance_arp(1);
+        self.next_sample()
+    }
+
+    /// Mix the next sample with the arpeggiator clock already advanced (see `tick()`).
+    fn next_sample(&mut self) -> f32 {
+        let tables = user_tables(&self.user_wavetables);
+        let sum: f32 = match self.sync_master {
+            Some(master) => {
+                // Master ticks first so a wrap resets the slave before the slave ticks
+                let master = master as usize;
+                let mut sum = self.voices[master].tick(tables);
+                if self.voices[master].wrapped() {
+                    self.voices[self.sync_slave as usize].sync_reset();
+                }
+                for (idx, voice) in self.voices.iter_mut().enumerate() {
+                    if idx != master {
+                        sum += voice.tick(tables);
+                    }
+                }
+                sum
+            }
+            None => self.voices.iter_mut().map(|v| v.tick(tables)).sum(),
+        };
+
+        // Normalize by the fade gains this sample was rendered with
+        self.refresh_mix();
+        let mixed = self.limiter.process(sum * self.mix_reciprocal);
+        let gain = MASTER_GAIN * self.next_master_volume();
+        self.process_master(mixed * gain) * self.next_mute_gain()
+    }
 
-        // active_count_reciprocal is pre-computed when voices toggle
-        sum * self.active_count_reciprocal * MASTER_GAIN
+    /// Advance master volume smoothing by one sample and return the new level.
+    #[inline(always)]
+    fn next_master_volume(&mut self) -> f32 {
+        self.master_volume_current = self.master_volume_current * VOLUME_SMOOTHING_COEFF
+            + self.master_volume_target * (1.0 - VOLUME_SMOOTHING_COEFF);
+        self.master_volume_current
+    }
+
+    /// Advance the flash-write mute ramp by one sample and return the new gain.
+    #[inline(always)]
+    fn next_mute_gain(&mut self) -> f32 {
+        self.mute_gain = if self.muting {
+            (self.mute_gain - self.mute_step).max(0.0)
+        } else {
+            (self.mute_gain + self.mute_step).min(1.0)
+        };
+        self.mute_gain
+    }
+
+    /// Run one mixed sample through the master effects chain.
+    #[inline(always)]
+    fn process_master(&mut self, x: f32) -> f32 {
+        let x = self.tremolo.process(x);
+        let x = self.bitcrusher.process(x);
+        self.dc_blocker.process(x)
+    }
+
+    /// Render a block of mixed mono samples.
+    ///
+    /// Each voice accumulates its whole block in a tight loop, then normalization,
+    /// limiting, master gain/volume and master effects are applied in a single final pass.
+    /// Produces the same signal as calling `tick()` once per sample: the block
+    /// is split where an arpeggiator step changes the gates, and while hard
+    /// sync is routed or a voice is fading it falls back to exactly that.
+    ///
+    /// # Arguments
+    /// * `out` - Destination block (overwritten, any length)
+    pub fn render_block(&mut self, out: &mut [f32]) {
+        let mut start = 0;
+        while start < out.len() {
+            // Clocked like tick(): a step taken here applies from this sample on,
+            // and the span runs up to the sample that takes the next one
+            self.advance_arp(1);
+            let remaining = out.len() - start;
+            let len = self
+                .arpeggiator
+                .frames_until_step()
+                .map_or(remaining, |frames| remaining.min(frames as usize + 1));
+            self.arpeggiator.advance(len as u32 - 1);
+            self.render_span(&mut out[start..start + len]);
+            start += len;
+        }
+    }
+
+    /// Render samples that share one set of gates (arpeggiator clock already advanced).
+    fn render_span(&mut self, out: &mut [f32]) {
+        // Hard sync needs master and slave interleaved sample by sample, and a
+        // fading voice moves the normalization every sample
+        if self.sync_master.is_some() || self.voices.iter().any(Voice::fading) {
+            for sample in out.iter_mut() {
+                *sample = self.next_sample();
+            }
+            return;
+        }
+
+        // No fade is moving, so the normalization holds for the whole span
+        out.fill(0.0);
+        let tables = user_tables(&self.user_wavetables);
+        for voice in self.voices.iter_mut() {
+            voice.render_add(out, tables);
+        }
+
+        let recip = self.mix_reciprocal;
+        for sample in out.iter_mut() {
+            let mixed = self.limiter.process(*sample * recip);
+            let gain = MASTER_GAIN * self.next_master_volume();
+            *sample = self.process_master(mixed * gain) * self.next_mute_gain();
+        }
     }
 
     /// Render audio into provided buffer.
     ///
-    /// Processes all pending control messages, generates audio samples,
-    /// converts to i16 stereo format, and writes to buffer.
+    /// Processes all pending control messages and any freshly loaded preset,
+    /// renders audio in blocks of RENDER_BLOCK_SIZE frames, converts them to
+    /// the configured output format, and writes every channel of each frame
+    /// (stereo pairs pass through the width control first).
+    ///
+    /// Any buffer length is accepted. When the buffer ends mid-frame, the frame
+    /// is rendered whole, its head fills the end of this buffer and its tail is
+    /// carried to the start of the next call, so the byte stream stays
+    /// frame-aligned across DMA wraps and no sample is dropped or repeated.
+    ///
+    /// While a flash write is pending (see `FlashMute`) the output fades to
+    /// silence, and the write is released once a whole DMA ring of silence
+    /// has been rendered.
     ///
     /// # Arguments
-    /// * `buffer` - Output buffer for i16 LE stereo audio (must be multiple of 4 bytes)
+    /// * `buffer` - Output buffer for LE PCM audio (any length, including 0)
     ///
     /// # Returns
-    /// Number of bytes written to buffer (will be multiple of 4)
+    /// Number of bytes written to buffer (always `buffer.len()`)
     pub fn render(&mut self, buffer: &mut [u8]) -> usize {
-        if buffer.len() < 4 {
-            return 0;
-        }
-
         // Process all pending control messages (non-blocking)
         // if clicks or issues, check this section because of 'while' drains everything
-        while let Ok(msg) = self.receiver.try_receive() {
+        while let Some(msg) = self.receiver.try_next() {
             self.process_message(msg);
         }
 
-        // Cache constant outside loop (computed once instead of per-sample)
-        const I16_MAX_F32: f32 = i16::MAX as f32;
+        // A preset the preset task finished reading applies like a message
+        if let Some(state) = self.loaded_preset.and_then(|loaded| loaded.try_take()) {
+            self.restore(&state);
+        }
+
+        // Only a buffer rendered from start to end at zero gain counts as silence
+        self.muting = self.flash_mute.is_some_and(FlashMute::requested);
+        let silent = self.muting && self.mute_gain == 0.0;
+
+        // 1. Finish the frame split at the end of the previous buffer
+        let mut written = self.carry_len.min(buffer.len());
+        if written > 0 {
+            let start = self.carry_start;
+            buffer[..written].copy_from_slice(&self.carry[start..start + written]);
+            self.carry_start += written;
+            self.carry_len -= written;
+        }
+
+        // 2. Whole frames
+        written += self.write_whole_frames(&mut buffer[written..]);
+
+        // 3. Trailing partial frame: render it whole, emit its head, carry its tail
+        let remaining = buffer.len() - written;
+        if remaining > 0 {
+            let frame_size = self.format.frame_size();
+            let mut frame = [0u8; MAX_FRAME_SIZE];
+            self.write_whole_frames(&mut frame[..frame_size]);
+            buffer[written..].copy_from_slice(&frame[..remaining]);
+            self.carry = frame;
+            self.carry_start = remaining;
+            self.carry_len = frame_size - remaining;
+            written = buffer.len();
+        }
+
+        if let Some(mute) = self.flash_mute {
+            self.track_silence(mute, silent, written);
+        }
+
+        // Once per render (not per sample): peaks cover the span just written
+        if let Some(levels) = self.level_meter {
+            for (idx, voice) in self.voices.iter_mut().enumerate() {
+                levels.store(idx, voice.take_peak());
+            }
+        }
+
+        written
+    }
 
-        // Generate audio for each stereo frame
-        for chunk in buffer.chunks_exact_mut(4) {
-            let sample_i16 = (self.tick() * I16_MAX_F32) as i16;
-            let bytes = sample_i16.to_le_bytes();
-            // Direct assignment is faster than copy_from_slice for 4 bytes
-            chunk[0] = bytes[0];
-            chunk[1] = bytes[1];
-            chunk[2] = bytes[0];
-            chunk[3] = bytes[1];
+    /// Count silent bytes and release a pending flash write once the DMA ring holds only silence.
+    fn track_silence(&mut self, mute: &FlashMute, silent: bool, written: usize) {
+        self.silent_bytes = if silent {
+            (self.silent_bytes + written).min(DMA_BUFFER_SIZE)
+        } else {
+            0
+        };
+        if self.silent_bytes == DMA_BUFFER_SIZE {
+            mute.confirm_silent();
         }
+    }
 
-        buffer.len() - (buffer.len() % 4)
+    /// Fill as many whole frames of the configured format as fit in `buffer`.
+    ///
+    /// # Returns
+    /// Number of bytes written (trailing partial frame is left untouched)
+    fn write_whole_frames(&mut self, buffer: &mut [u8]) -> usize {
+        match self.format {
+            OutputFormat::Stereo16 => self.write_frames::<2, 2>(buffer, f32_to_i16_le),
+            OutputFormat::Stereo24 => self.write_frames::<4, 2>(buffer, f32_to_i24_le),
+            OutputFormat::Mono16 => self.write_frames::<2, 1>(buffer, f32_to_i16_le),
+        }
+    }
+
+    /// Fill whole frames of `C` channels with `N`-byte samples produced by `convert`.
+    ///
+    /// Renders into a stack block first, then converts the block in a
+    /// separate pass. Monomorphized per layout so the frame stride is a
+    /// compile-time constant and the per-channel copies unroll into direct byte stores.
+    ///
+    /// # Returns
+    /// Number of bytes written (trailing partial frame is left untouched)
+    #[inline(always)]
+    fn write_frames<const N: usize, const C: usize>(
+        &mut self,
+        buffer: &mut [u8],
+        convert: fn(f32) -> [u8; N],
+    ) -> usize {
+        let frame_size = N * C;
+        let written = buffer.len() - (buffer.len() % frame_size);
+        let mut block = [0.0f32; RENDER_BLOCK_SIZE];
+
+        for chunk in buffer[..written].chunks_mut(RENDER_BLOCK_SIZE * frame_size) {
+            let block = &mut block[..chunk.len() / frame_size];
+            self.render_block(block);
+
+            for (frame, &sample) in chunk.chunks_exact_mut(frame_size).zip(block.iter()) {
+                if C == 2 {
+                    // The mix is mono until per-voice panning exists, so L = R going in
+                    let (left, right) = self.stereo_width.process(sample, sample);
+                    let (left_bytes, right_bytes) = frame.split_at_mut(N);
+                    left_bytes.copy_from_slice(&convert(left));
+                    right_bytes.copy_from_slice(&convert(right));
+                } else {
+                    let bytes = convert(sample);
+                    for channel in frame.chunks_exact_mut(N) {
+                        channel.copy_from_slice(&bytes);
+                    }
+                }
+            }
+        }
+
+        written
+    }
+}
+
+/// User wavetable slots as a slice (empty when no storage is attached).
+///
+/// Takes the field rather than `&self` so voices can be borrowed mutably alongside it.
+fn user_tables<'a>(tables: &'a Option<&'static mut UserWavetables>) -> &'a [Wavetable] {
+    match tables {
+        Some(tables) => &tables[..],
+        None => &[],
+    }
+}
+
+/// Distinct, non-zero noise seed for each voice (golden-ratio spacing).
+fn noise_seed(voice_idx: usize) -> u32 {
+    (voice_idx as u32 + 1).wrapping_mul(0x9E37_79B9)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::SAMPLE_RATE;
+    use crate::message::QueueSource;
+    use crate::oscillator::{Waveform, SINE};
+    use core::future::Future;
+    use core::pin::pin;
+    use core::task::{Context, Waker};
+
+    /// Engine fed from an in-memory queue preloaded with `messages`.
+    fn engine_with(messages: &[Message]) -> Engine<QueueSource<16>> {
+        let mut source = QueueSource::new();
+        for &msg in messages {
+            source.push(msg).unwrap();
+        }
+        Engine::new(SAMPLE_RATE as f32, OutputFormat::Stereo16, source)
+    }
+
+    #[test]
+    fn render_is_silent_until_a_voice_is_toggled_on() {
+        let mut engine = engine_with(&[]);
+        let mut buffer = [0xAAu8; 2044];
+        assert_eq!(engine.render(&mut buffer), buffer.len());
+        assert!(buffer.iter().all(|&b| b == 0));
+
+        engine.receiver.push(Message::ToggleVoice(0)).unwrap();
+        engine.render(&mut buffer);
+        assert!(engine.voices[0].active);
+        assert!(buffer.iter().any(|&b| b != 0));
+    }
+
+    #[test]
+    fn render_drains_every_queued_message() {
+        let mut engine = engine_with(&[
+            Message::SelectVoice(1),
+            Message::SetFrequency(440.0),
+            Message::SetVolume(0.5),
+        ]);
+        engine.render(&mut [0u8; 4]);
+
+        assert!(engine.receiver.try_next().is_none());
+        assert_eq!(engine.voices[1].frequency(), 440.0);
+        assert_eq!(engine.voices[1].volume(), 0.5);
+        assert_eq!(engine.voices[0].frequency(), STARTING_FREQUENCY);
+    }
+
+    /// Every output layout render() supports.
+    const FORMATS: [OutputFormat; 3] = [
+        OutputFormat::Stereo16,
+        OutputFormat::Stereo24,
+        OutputFormat::Mono16,
+    ];
+
+    /// Buffer lengths named by the streaming contract: empty, partial frames, whole frames, DMA size.
+    const LENGTHS: [usize; 5] = [0, 1, 3, 4, 2044];
+
+    /// Engine in `format` with two voices sounding at unrelated pitches.
+    fn playing_engine(format: OutputFormat) -> Engine<QueueSource<16>> {
+        let mut engine = Engine::new(SAMPLE_RATE as f32, format, QueueSource::new());
+        for (idx, freq) in [(0, 440.0), (1, 97.0)] {
+            engine.process_message(Message::ToggleVoice(idx));
+            engine.selected_voice = Some(idx);
+            engine.process_message(Message::SetFrequency(freq));
+        }
+        engine
+    }
+
+    #[test]
+    fn render_fills_buffers_of_any_length() {
+        for format in FORMATS {
+            let mut engine = playing_engine(format);
+            for len in LENGTHS {
+                let mut buffer = vec![0u8; len];
+                assert_eq!(engine.render(&mut buffer), len, "{format:?}, {len} bytes");
+            }
+        }
+    }
+
+    #[test]
+    fn split_renders_match_one_contiguous_render() {
+        // The named lengths twice, then 1..=17 so the carry starts at every offset of every frame size
+        let lengths: Vec<usize> = LENGTHS.iter().chain(&LENGTHS).copied().chain(1..=17).collect();
+
+        for format in FORMATS {
+            let mut split = playing_engine(format);
+            let mut stream = Vec::new();
+            for &len in &lengths {
+                let mut buffer = vec![0xAAu8; len];
+                split.render(&mut buffer);
+                stream.extend_from_slice(&buffer);
+            }
+
+            let mut contiguous = playing_engine(format);
+            let mut reference = vec![0u8; stream.len()];
+            contiguous.render(&mut reference);
+
+            assert!(reference.iter().any(|&b| b != 0), "{format:?} rendered silence");
+            assert_eq!(stream, reference, "{format:?}");
+        }
+    }
+
+    #[test]
+    fn block_rendering_matches_ticks_across_arpeggiator_steps() {
+        let arpeggiated = || {
+            let mut engine = playing_engine(OutputFormat::Mono16);
+            engine.process_message(Message::ToggleVoice(2));
+            engine.process_message(Message::SetArpEnabled(true));
+            engine.process_message(Message::SetArpStepMs(20.0));
+            engine
+        };
+        // About ten steps, rendered in blocks that straddle the step boundaries
+        let samples = SAMPLE_RATE as usize / 5;
+
+        let mut ticked = arpeggiated();
+        let reference: Vec<f32> = (0..samples).map(|_| ticked.tick()).collect();
+
+        for block_len in [1, 37, RENDER_BLOCK_SIZE] {
+            let mut blocked = arpeggiated();
+            let mut rendered = vec![0.0; samples];
+            for block in rendered.chunks_mut(block_len) {
+                blocked.render_block(block);
+            }
+            assert_eq!(rendered, reference, "{block_len}-sample blocks");
+        }
+    }
+
+    /// Largest per-sample change of the normalization over `samples` ticks, and its final value.
+    fn normalization_trace(engine: &mut Engine<QueueSource<16>>, samples: usize) -> (f32, f32) {
+        let mut largest_step = 0.0f32;
+        for _ in 0..samples {
+            let before = engine.mix_reciprocal;
+            engine.tick();
+            largest_step = largest_step.max((engine.mix_reciprocal - before).abs());
+        }
+        (largest_step, engine.mix_reciprocal)
+    }
+
+    #[test]
+    fn normalization_glides_as_voices_fade_in_and_out() {
+        let fade_samples = (FADE_MS * SAMPLE_RATE as f32 / 1000.0) as usize;
+        let mut engine = engine_with(&[]);
+        engine.process_message(Message::ToggleVoice(0));
+        let (_, settled) = normalization_trace(&mut engine, 2 * fade_samples);
+        assert_eq!(settled, 1.0);
+
+        // 1/1 → 1/2 spread over the fade, never a single step
+        engine.process_message(Message::ToggleVoice(1));
+        let (step, settled) = normalization_trace(&mut engine, 2 * fade_samples);
+        assert!(step < 2.0 / fade_samples as f32, "step {step}");
+        assert_eq!(settled, 0.5);
+
+        engine.process_message(Message::ToggleVoice(1));
+        let (step, settled) = normalization_trace(&mut engine, 2 * fade_samples);
+        assert!(step < 2.0 / fade_samples as f32, "step {step}");
+        assert_eq!(settled, 1.0);
+    }
+
+    #[test]
+    fn arpeggiator_toggle_ramps_the_normalization() {
+        let fade_samples = (FADE_MS * SAMPLE_RATE as f32 / 1000.0) as usize;
+        let mut engine = engine_with(&[]);
+        for idx in 0..3 {
+            engine.process_message(Message::ToggleVoice(idx));
+        }
+        let (_, settled) = normalization_trace(&mut engine, 2 * fade_samples);
+        assert_eq!(settled, 1.0 / 3.0);
+
+        // Gated voices fade out, so 1/3 → 1/1 glides instead of tripling the gain at once
+        engine.process_message(Message::SetArpEnabled(true));
+        let (step, settled) = normalization_trace(&mut engine, 2 * fade_samples);
+        assert!(step < 3.0 / fade_samples as f32, "step {step}");
+        assert_eq!(settled, 1.0);
+
+        engine.process_message(Message::SetArpEnabled(false));
+        let (step, settled) = normalization_trace(&mut engine, 2 * fade_samples);
+        assert!(step < 3.0 / fade_samples as f32, "step {step}");
+        assert_eq!(settled, 1.0 / 3.0);
+    }
+
+    #[test]
+    fn notes_prefer_silent_voices_over_releasing_ones() {
+        let fade_samples = (FADE_MS * SAMPLE_RATE as f32 / 1000.0) as usize;
+        let mut engine = engine_with(&[]);
+        for note in [60, 64, 67] {
+            engine.process_message(Message::NoteOn { note, velocity: 100 });
+        }
+
+        // Voice 1 rings out completely, then voice 0 (the older note) starts its release
+        engine.process_message(Message::NoteOff { note: 64 });
+        for _ in 0..2 * fade_samples {
+            engine.tick();
+        }
+        engine.process_message(Message::NoteOff { note: 60 });
+        engine.tick();
+        assert!(engine.voices[0].is_sounding() && !engine.voices[1].is_sounding());
+
+        // The silent voice is taken, so the release keeps ringing
+        engine.process_message(Message::NoteOn { note: 72, velocity: 100 });
+        assert_eq!(engine.voices[1].note(), Some(72));
+        assert!(!engine.voices[0].active);
+
+        // With nothing silent left, the releasing voice goes before a held one
+        engine.process_message(Message::NoteOn { note: 76, velocity: 100 });
+        assert_eq!(engine.voices[0].note(), Some(76));
+        assert_eq!(engine.voices[2].note(), Some(67));
+    }
+
+    #[test]
+    fn stealing_a_voice_fades_it_out_before_the_new_note() {
+        let fade_samples = (FADE_MS * SAMPLE_RATE as f32 / 1000.0) as usize;
+        let mut engine = engine_with(&[]);
+        for note in [60, 64, 67] {
+            engine.process_message(Message::NoteOn { note, velocity: 127 });
+        }
+        let mut previous = 0.0;
+        let mut step_over = |engine: &mut Engine<QueueSource<16>>, samples: usize| {
+            let mut largest = 0.0f32;
+            for _ in 0..samples {
+                let sample = engine.tick();
+                largest = largest.max((sample - previous).abs());
+                previous = sample;
+            }
+            largest
+        };
+        step_over(&mut engine, 10 * fade_samples);
+        let baseline = step_over(&mut engine, 10 * fade_samples);
+
+        // Every voice is busy, so the oldest note (60 on voice 0) is stolen
+        engine.process_message(Message::NoteOn { note: 84, velocity: 127 });
+        assert_eq!(engine.voices[0].note(), Some(84));
+        assert_eq!(engine.voices[0].frequency(), note_to_frequency(60));
+
+        let step = step_over(&mut engine, 4 * fade_samples);
+        assert_eq!(engine.voices[0].frequency(), note_to_frequency(84));
+        assert_eq!(engine.voices[0].fade(), 1.0);
+
+        // The higher note steepens the settled waveform, so bound by both chords
+        let baseline = baseline.max(step_over(&mut engine, 10 * fade_samples));
+        assert!(step <= 1.1 * baseline, "steal step {step} vs {baseline}");
+    }
+
+    #[test]
+    fn restriking_a_note_leaves_every_other_voice_untouched() {
+        let chord = || {
+            let mut engine = engine_with(&[]);
+            for note in [60, 64, 67] {
+                engine.process_message(Message::NoteOn { note, velocity: 100 });
+            }
+            for _ in 0..1000 {
+                engine.tick();
+            }
+            engine
+        };
+        // Per-sample output magnitude of each voice (take_peak right after every tick)
+        let trace = |engine: &mut Engine<QueueSource<16>>| -> Vec<[f32; VOICE_COUNT]> {
+            (0..2000)
+                .map(|_| {
+                    engine.tick();
+                    from_fn(|idx| engine.voices[idx].take_peak())
+                })
+                .collect()
+        };
+        let mut control = chord();
+        let reference = trace(&mut control);
+
+        // Same velocity: the sounding voice keeps its phase, so nothing changes at all
+        let mut engine = chord();
+        engine.process_message(Message::NoteOn { note: 60, velocity: 100 });
+        assert_eq!(trace(&mut engine), reference);
+
+        // Softer: only the re-struck voice moves
+        let mut engine = chord();
+        engine.process_message(Message::NoteOn { note: 60, velocity: 40 });
+        let softer = trace(&mut engine);
+        assert!(softer.iter().zip(&reference).all(|(a, b)| a[1..] == b[1..]));
+        assert!(softer.last().unwrap()[0] < reference.last().unwrap()[0]);
+    }
+
+    #[test]
+    fn sync_rejects_a_voice_syncing_itself() {
+        let mut engine = engine_with(&[]);
+        engine.process_message(Message::SetSync { master: Some(1), slave: 1 });
+        assert_eq!(engine.sync_master, None);
+
+        // An existing routing survives a rejected one
+        engine.process_message(Message::SetSync { master: Some(0), slave: 2 });
+        engine.process_message(Message::SetSync { master: Some(2), slave: 2 });
+        assert_eq!((engine.sync_master, engine.sync_slave), (Some(0), 2));
+    }
+
+    #[test]
+    fn output_is_unchanged_without_a_sync_routing() {
+        let mut plain = playing_engine(OutputFormat::Stereo16);
+        let mut unrouted = playing_engine(OutputFormat::Stereo16);
+        for msg in [
+            Message::SetSync { master: Some(0), slave: 1 },
+            Message::SetSync { master: None, slave: 1 },
+            Message::SetSync { master: Some(1), slave: 1 },
+            Message::SetSync { master: Some(0), slave: VOICE_COUNT as u8 },
+        ] {
+            unrouted.process_message(msg);
+        }
+        assert_eq!(unrouted.sync_master, None);
+
+        for _ in 0..SAMPLE_RATE as usize / 10 {
+            assert_eq!(unrouted.tick().to_bits(), plain.tick().to_bits());
+        }
+    }
+
+    #[test]
+    fn sync_restarts_the_slave_when_the_master_wraps() {
+        // fs / 64 wraps the master exactly every 64 samples; the slave's own period is 51.2
+        let slave_cycles = |sync: bool| {
+            let mut engine = engine_with(&[]);
+            let master_freq = SAMPLE_RATE as f32 / 64.0;
+            for (idx, freq) in [(0, master_freq), (1, 1.25 * master_freq)] {
+                engine.process_message(Message::ToggleVoice(idx));
+                engine.selected_voice = Some(idx);
+                engine.process_message(Message::SetFrequency(freq));
+            }
+            if sync {
+                engine.process_message(Message::SetSync { master: Some(0), slave: 1 });
+            }
+            // Let the fades and volume smoothing settle so only the phase shapes the output
+            for _ in 0..SAMPLE_RATE as usize {
+                engine.tick();
+            }
+            engine.voices[1].take_peak();
+            let mut cycle = || -> [f32; 64] {
+                from_fn(|_| {
+                    engine.tick();
+                    engine.voices[1].take_peak()
+                })
+            };
+            (cycle(), cycle())
+        };
+
+        let (first, second) = slave_cycles(true);
+        assert_eq!(first, second);
+        let (first, second) = slave_cycles(false);
+        assert_ne!(first, second);
+    }
+
+    #[test]
+    fn load_wavetable_copies_the_staged_table() {
+        let staging: &'static WavetableStaging = Box::leak(Box::new(WavetableStaging::new()));
+        let mut engine = engine_with(&[Message::LoadWavetable(1), Message::LoadWavetable(9)]);
+        engine.set_user_wavetables(Box::leak(Box::new([SINE; USER_WAVETABLE_SLOTS])));
+        engine.set_wavetable_staging(staging);
+
+        let mut table = [0.0; WAVETABLE_SIZE];
+        table[..WAVETABLE_SIZE / 2].fill(0.5);
+        table[WAVETABLE_SIZE / 2..].fill(-0.5);
+        staging.write(&table);
+
+        // Out-of-range slot 9 is ignored, slot 0 keeps its sine
+        engine.render(&mut [0u8; 4]);
+        let tables = engine.user_wavetables.as_deref().unwrap();
+        assert_eq!(tables[0], SINE);
+        assert_eq!(tables[1], table);
+    }
+
+    #[test]
+    fn voice_parameters_need_a_selection() {
+        let mut engine = engine_with(&[]);
+        engine.process_message(Message::SetFrequency(440.0));
+        assert!(engine.voices.iter().all(|v| v.frequency() == STARTING_FREQUENCY));
+
+        engine.process_message(Message::SelectVoice(2));
+        engine.process_message(Message::SetFrequency(440.0));
+        assert_eq!(engine.voices[2].frequency(), 440.0);
+    }
+
+    #[test]
+    fn restore_applies_every_field_and_rederives_the_mix() {
+        let mut edited = playing_engine(OutputFormat::Stereo16);
+        edited.selected_voice = Some(1);
+        for msg in [
+            Message::SetWaveform(Waveform::Square),
+            Message::SetPulseWidth(0.3),
+            Message::SetSubLevel(0.5),
+            Message::SetSubWaveform(Waveform::Sine),
+            Message::SetCutoff(900.0),
+            Message::SetResonance(0.7),
+            Message::SetKeyTrack(0.25),
+            Message::SetRetrigger(true),
+        ] {
+            edited.process_message(msg);
+        }
+        let state = edited.snapshot();
+
+        // Three voices fully faded in, then a stale cache
+        let fade_samples = (FADE_MS * SAMPLE_RATE as f32 / 1000.0) as usize;
+        let mut engine = playing_engine(OutputFormat::Stereo16);
+        engine.process_message(Message::ToggleVoice(2));
+        normalization_trace(&mut engine, 2 * fade_samples);
+        engine.mix_weight = 0.0;
+        engine.mix_reciprocal = 1.0;
+
+        engine.restore(&state);
+        assert_eq!(engine.snapshot(), state);
+        // Voice 2 is switched off by the preset but is still at full fade
+        assert_eq!(engine.mix_weight, 3.0);
+        assert_eq!(engine.mix_reciprocal, 1.0 / 3.0);
+
+        let (_, settled) = normalization_trace(&mut engine, 2 * fade_samples);
+        assert_eq!(settled, 0.5);
+    }
+
+    /// Left-channel samples of a frame-aligned Stereo16 stream.
+    fn left_samples(stream: &[u8]) -> Vec<f32> {
+        stream
+            .chunks_exact(4)
+            .map(|frame| i16::from_le_bytes([frame[0], frame[1]]) as f32 / i16::MAX as f32)
+            .collect()
+    }
+
+    /// Largest change between neighbouring samples.
+    fn largest_step(samples: &[f32]) -> f32 {
+        samples.windows(2).map(|pair| (pair[1] - pair[0]).abs()).fold(0.0, f32::max)
+    }
+
+    #[test]
+    fn flash_writes_wait_for_a_ring_of_silence() {
+        static REQUESTS: PresetRequests = PresetRequests::new();
+        static LOADED: LoadedPreset = LoadedPreset::new();
+        static MUTE: FlashMute = FlashMute::new();
+
+        let mut engine = playing_engine(OutputFormat::Stereo16);
+        engine.set_preset_channels(&REQUESTS, &LOADED, &MUTE);
+        let mut buffer = [0u8; 2048];
+        engine.render(&mut buffer);
+        engine.render(&mut buffer);
+        let baseline = largest_step(&left_samples(&buffer));
+
+        // Poll the preset task's side of the handshake between renders
+        let mut cx = Context::from_waker(Waker::noop());
+        let mut muted = pin!(MUTE.mute());
+        let mut stream = Vec::new();
+        while muted.as_mut().poll(&mut cx).is_pending() {
+            engine.render(&mut buffer);
+            stream.extend_from_slice(&buffer);
+            assert!(stream.len() < 10 * DMA_BUFFER_SIZE, "mute never confirmed");
+        }
+
+        // Whatever the ring replays during the write is silence, reached through a fade
+        assert!(stream.len() > DMA_BUFFER_SIZE);
+        assert!(stream[stream.len() - DMA_BUFFER_SIZE..].iter().all(|&b| b == 0));
+        let step = largest_step(&left_samples(&stream));
+        assert!(step <= baseline, "fade-out step {step} vs {baseline}");
+
+        MUTE.unmute();
+        let mut stream = Vec::new();
+        for _ in 0..4 {
+            engine.render(&mut buffer);
+            stream.extend_from_slice(&buffer);
+        }
+        assert!(buffer.iter().any(|&b| b != 0));
+        let step = largest_step(&left_samples(&stream));
+        assert!(step <= baseline, "fade-in step {step} vs {baseline}");
     }
 }
@@ -0,0 +1,137 @@
+//! Per-voice resonant low-pass filter (topology-preserving state variable filter).
+
+use crate::config::{FILTER_CUTOFF_MAX, FILTER_CUTOFF_MIN, FILTER_RESONANCE_MAX};
+use micromath::F32Ext;
+
+/// Two-pole resonant low-pass.
+///
+/// Trapezoidal-integrated SVF: stable under fast cutoff changes, with
+/// coefficients recomputed only when cutoff or resonance change (never per
+/// sample). Fully open (FILTER_CUTOFF_MAX, no resonance) it is bypassed, so
+/// voices that never touch the filter sound exactly as before.
+pub struct Filter {
+    /// Sample rate in Hz (for converting cutoff to the warped gain)
+    sample_rate: f32,
+    /// Cutoff frequency in Hz
+    cutoff: f32,
+    /// Resonance (0.0 = none, 1.0 = FILTER_RESONANCE_MAX)
+    resonance: f32,
+    /// Damping (2.0 = no resonance, lower = sharper peak)
+    k: f32,
+    /// Derived coefficients (see `update_coefficients`)
+    a1: f32,
+    a2: f32,
+    a3: f32,
+    /// Integrator states
+    ic1: f32,
+    ic2: f32,
+    /// Fully open and not resonant: pass the input through
+    bypass: bool,
+}
+
+impl Filter {
+    /// Create a fully open filter (bypassed).
+    ///
+    /// # Arguments
+    /// * `sample_rate` - Audio sample rate in Hz
+    pub fn new(sample_rate: f32) -> Self {
+        let mut filter = Self {
+            sample_rate,
+            cutoff: FILTER_CUTOFF_MAX,
+            resonance: 0.0,
+            k: 2.0,
+            a1: 0.0,
+            a2: 0.0,
+            a3: 0.0,
+            ic1: 0.0,
+            ic2: 0.0,
+            bypass: true,
+        };
+        filter.update_coefficients();
+        filter
+    }
+
+    /// Set cutoff frequency in Hz (clamped to FILTER_CUTOFF_MIN..=FILTER_CUTOFF_MAX).
+    pub fn set_cutoff(&mut self, cutoff: f32) {
+        self.cutoff = cutoff.clamp(FILTER_CUTOFF_MIN, FILTER_CUTOFF_MAX);
+        self.update_coefficients();
+    }
+
+    /// Cutoff frequency in Hz.
+    pub fn cutoff(&self) -> f32 {
+        self.cutoff
+    }
+
+    /// Set resonance (0.0 to 1.0).
+    pub fn set_resonance(&mut self, resonance: f32) {
+        self.resonance = resonance.clamp(0.0, 1.0);
+        self.k = 2.0 * (1.0 - self.resonance * FILTER_RESONANCE_MAX);
+        self.update_coefficients();
+    }
+
+    /// Resonance (0.0 to 1.0).
+    pub fn resonance(&self) -> f32 {
+        self.resonance
+    }
+
+    /// Process one sample.
+    #[inline(always)]
+    pub fn process(&mut self, x: f32) -> f32 {
+        if self.bypass {
+            // Track the input, so engaging continues from the signal instead of from rest
+            self.ic2 = x;
+            return x;
+        }
+
+        let v3 = x - self.ic2;
+        let v1 = self.a1 * self.ic1 + self.a2 * v3;
+        let v2 = self.ic2 + self.a2 * self.ic1 + self.a3 * v3;
+        self.ic1 = 2.0 * v1 - self.ic1;
+        self.ic2 = 2.0 * v2 - self.ic2;
+        v2
+    }
+
+    /// Recompute the SVF coefficients from cutoff and damping.
+    fn update_coefficients(&mut self) {
+        let was_bypassed = self.bypass;
+        self.bypass = self.cutoff >= FILTER_CUTOFF_MAX && self.resonance == 0.0;
+
+        // Engage from the last bypassed input (held in ic2) so the output stays
+        // continuous; only the band-pass state from an earlier engagement is stale
+        if was_bypassed && !self.bypass {
+            self.ic1 = 0.0;
+        }
+
+        let g = (core::f32::consts::PI * self.cutoff / self.sample_rate).tan();
+        self.a1 = 1.0 / (1.0 + g * (g + self.k));
+        self.a2 = g * self.a1;
+        self.a3 = g * self.a2;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE_RATE: f32 = 48_000.0;
+
+    #[test]
+    fn engaging_the_filter_continues_the_signal() {
+        // 200 Hz sine; on its peak the per-sample change is far below 0.01
+        let sine = |n: usize| (2.0 * core::f32::consts::PI * 200.0 * n as f32 / SAMPLE_RATE).sin();
+        let mut filter = Filter::new(SAMPLE_RATE);
+        let peak = 4 * 240 + 60;
+        for n in 0..peak {
+            assert_eq!(filter.process(sine(n)), sine(n));
+        }
+
+        // Starting from rest would drop the output from ~1.0 towards 0.0 in one sample
+        filter.set_cutoff(FILTER_CUTOFF_MAX * 0.5);
+        let mut previous = sine(peak - 1);
+        for n in peak..peak + 240 {
+            let y = filter.process(sine(n));
+            assert!((y - previous).abs() < 0.05, "jump {} at sample {n}", y - previous);
+            previous = y;
+        }
+    }
+}
@@ -3,13 +3,20 @@
 use esp_hal::{
     analog::adc::{Adc, AdcCalCurve, AdcChannel, AdcConfig, AdcPin, Attenuation},
     dma::DmaDescriptor,
-    gpio::AnalogPin,
+    gpio::{interconnect::PeripheralOutput, AnalogPin, AnyPin},
     i2s::master::{asynch::I2sWriteDmaTransferAsync, DataFormat, I2s, Standard},
-    peripherals::ADC1,
+    ledc::{
+        channel::{self, ChannelIFace},
+        timer::{self, TimerIFace},
+        LSGlobalClkSource, Ledc, LowSpeed,
+    },
+    peripherals::{ADC1, I2S0, LEDC},
     time::Rate,
     Blocking,
 };
-use crate::config::SAMPLE_RATE;
+use static_cell::StaticCell;
+use crate::audio_util::OutputFormat;
+use crate::config::{LED_PWM_FREQUENCY_KHZ, VOICE_COUNT};
 
 /// Slim controller: own only the ADC peripheral.
 pub struct AdcBus {
@@ -18,54 +25,106 @@ pub struct AdcBus {
 
 /// Type aliases to make signatures readable.
 pub type PotPin<P> = AdcPin<P, ADC1<'static>, AdcCalCurve<ADC1<'static>>>;
+pub type LedChannel = channel::Channel<'static, LowSpeed>;
+
+/// LEDC channels assigned to the voice LEDs, in voice order.
+const LED_CHANNELS: [channel::Number; 8] = [
+    channel::Number::Channel0,
+    channel::Number::Channel1,
+    channel::Number::Channel2,
+    channel::Number::Channel3,
+    channel::Number::Channel4,
+    channel::Number::Channel5,
+    channel::Number::Channel6,
+    channel::Number::Channel7,
+];
+
+const _: () = assert!(VOICE_COUNT <= LED_CHANNELS.len(), "one LEDC channel per voice LED");
+
+/// LEDC driver and shared timer; channels borrow both for 'static.
+static LEDC_DRIVER: StaticCell<Ledc<'static>> = StaticCell::new();
+static LED_TIMER: StaticCell<timer::Timer<'static, LowSpeed>> = StaticCell::new();
 
 /// Initialize I2S audio output and return ready-to-use DMA transaction.
 ///
-/// Configures I2S in Philips standard stereo mode (16-bit samples),
-/// sets up circular DMA transfer, and returns transaction ready for push_with().
+/// Configures I2S in Philips standard mode at the given sample rate and output
+/// format, sets up circular DMA transfer, and returns transaction ready for push_with().
 ///
-/// # Pin Configuration
-/// - BCLK (bit clock) => GPIO7
-/// - WS (word select) => GPIO8
-/// - DOUT (data out) => GPIO9
+/// For mono formats the TX unit is switched to mono mode, so each 16-bit sample
+/// in the DMA buffer is sent on both the left and right slots.
+///
+/// Pins are chosen by the caller (any output-capable GPIO routed through the
+/// GPIO matrix); the board default lives in `main.rs`.
 ///
 /// # Arguments
 /// * `i2s0` - I2S0 peripheral
 /// * `dma_channel` - DMA channel for circular buffer
-/// * `gpio7` - BCLK pin
-/// * `gpio8` - WS pin
-/// * `gpio9` - DOUT pin
+/// * `bclk` - Bit clock output pin
+/// * `ws` - Word select (LR clock) output pin
+/// * `dout` - Serial data output pin
+/// * `sample_rate` - Output sample rate in Hz (must match the engine's rate)
+/// * `format` - Output PCM layout (must match the engine's format)
 /// * `tx_buffer` - DMA transmit buffer (from dma_circular_buffers! macro)
 /// * `tx_descriptors` - DMA descriptors (from dma_circular_buffers! macro)
 ///
 /// # Returns
 /// Configured I2S DMA transaction ready for audio rendering
 pub fn setup_audio(
-    i2s0: esp_hal::peripherals::I2S0<'static>,
+    i2s0: I2S0<'static>,
     dma_channel: esp_hal::peripherals::DMA_CH0<'static>,
-    gpio7: esp_hal::peripherals::GPIO7<'static>,
-    gpio8: esp_hal::peripherals::GPIO8<'static>,
-    gpio9: esp_hal::peripherals::GPIO9<'static>,
+    bclk: impl PeripheralOutput<'static>,
+    ws: impl PeripheralOutput<'static>,
+    dout: impl PeripheralOutput<'static>,
+    sample_rate: u32,
+    format: OutputFormat,
     tx_buffer: &'static mut [u8],
     tx_descriptors: &'static mut [DmaDescriptor],
 ) -> I2sWriteDmaTransferAsync<'static, &'static mut [u8]> {
     let i2s_tx = I2s::new(
         i2s0,
         Standard::Philips,
-        DataFormat::Data16Channel16,
-        Rate::from_hz(SAMPLE_RATE),
+        data_format(format),
+        Rate::from_hz(sample_rate),
         dma_channel,
     )
     .into_async()
     .i2s_tx
-    .with_bclk(gpio7)
-    .with_ws(gpio8)
-    .with_dout(gpio9)
+    .with_bclk(bclk)
+    .with_ws(ws)
+    .with_dout(dout)
     .build(tx_descriptors);
 
+    if format.is_mono() {
+        enable_tx_mono();
+    }
+
     i2s_tx.write_dma_circular_async(tx_buffer).unwrap()
 }
 
+/// I2S data format matching an output layout.
+const fn data_format(format: OutputFormat) -> DataFormat {
+    match format {
+        OutputFormat::Stereo16 | OutputFormat::Mono16 => DataFormat::Data16Channel16,
+        OutputFormat::Stereo24 => DataFormat::Data32Channel32,
+    }
+}
+
+/// Switch the I2S0 transmitter to mono mode.
+///
+/// esp-hal always configures TX for two independent slots, so this sets the
+/// same bits as ESP-IDF's `i2s_ll_tx_enable_mono_mode`: `tx_mono` fetches a
+/// single sample per frame and `tx_chan_equal` repeats it on the second slot.
+/// Must be called before the DMA transfer starts.
+fn enable_tx_mono() {
+    let regs = I2S0::regs();
+    regs.tx_conf().modify(|_, w| {
+        w.tx_mono().set_bit();
+        w.tx_mono_fst_vld().set_bit();
+        w.tx_chan_equal().set_bit()
+    });
+    regs.tx_conf().modify(|_, w| w.tx_update().set_bit());
+}
+
 /// Generic setup: you pass *any* two GPIOs that are ADC1-capable.
 /// We return the ADC bus + two configured pins (with calibration).
 pub fn setup_adc<PF, PV>(
@@ -86,3 +145,42 @@ where
     let adc = Adc::new(adc1, cfg);
     (AdcBus { adc }, freq_pin, vol_pin)
 }
+
+/// Initialize one PWM channel per voice LED.
+///
+/// All channels share low-speed timer 0 at LED_PWM_FREQUENCY_KHZ with 8-bit
+/// duty resolution and start dark. Must be called at most once (the driver
+/// and timer are kept in statics so the channels can outlive this call).
+///
+/// # Arguments
+/// * `ledc` - LEDC peripheral
+/// * `pins` - LED output pins, in voice order
+///
+/// # Returns
+/// Configured channels, ready for `led_task`
+pub fn setup_leds(ledc: LEDC<'static>, pins: [AnyPin<'static>; VOICE_COUNT]) -> [LedChannel; VOICE_COUNT] {
+    let ledc = LEDC_DRIVER.init(Ledc::new(ledc));
+    ledc.set_global_slow_clock(LSGlobalClkSource::APBClk);
+
+    let led_timer = LED_TIMER.init(ledc.timer::<LowSpeed>(timer::Number::Timer0));
+    led_timer
+        .configure(timer::config::Config {
+            duty: timer::config::Duty::Duty8Bit,
+            clock_source: timer::LSClockSource::APBClk,
+            frequency: Rate::from_khz(LED_PWM_FREQUENCY_KHZ),
+        })
+        .unwrap();
+    let led_timer: &'static timer::Timer<'static, LowSpeed> = led_timer;
+
+    let mut pins = pins.into_iter();
+    core::array::from_fn(|idx| {
+        let mut led = ledc.channel(LED_CHANNELS[idx], pins.next().unwrap());
+        led.configure(channel::config::Config {
+            timer: led_timer,
+            duty_pct: 0,
+            pin_config: channel::config::PinConfig::PushPull,
+        })
+        .unwrap();
+        led
+    })
+}
@@ -1,9 +1,20 @@
-#![no_std]
+#![cfg_attr(not(test), no_std)]
+// Host tests link std, whose inherent f32 math shadows micromath's F32Ext
+#![cfg_attr(test, allow(unused_imports))]
 
+pub mod arpeggiator;
+pub mod audio_util;
 pub mod config;
+#[cfg(target_arch = "xtensa")]
 pub mod controls;
+pub mod effects;
 pub mod engine;
+pub mod filter;
+#[cfg(target_arch = "xtensa")]
 pub mod hardware;
 pub mod message;
+pub mod meter;
 pub mod oscillator;
-pub mod voice;
\ No newline at end of file
+pub mod pitch;
+pub mod preset;
+pub mod voice;
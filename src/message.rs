@@ -1,5 +1,11 @@
 //! Message types for lock-free communication between control tasks and audio task.
 
+use crate::arpeggiator::ArpPattern;
+use crate::oscillator::Waveform;
+use embassy_sync::blocking_mutex::raw::RawMutex;
+use embassy_sync::channel::Receiver;
+use heapless::Deque;
+
 /// Messages sent from control tasks (buttons, pots, encoders) to audio task.
 #[derive(Debug, Clone, Copy)]
 pub enum Message {
@@ -17,4 +23,168 @@ pub enum Message {
     /// Set volume of currently selected voice (0.0 to 1.0)
     /// Only applies if a voice is selected (Some(n))
     SetVolume(f32),
+
+    /// Set waveform of currently selected voice
+    /// Only applies if a voice is selected (Some(n))
+    SetWaveform(Waveform),
+
+    /// Set square-wave pulse width of currently selected voice (0.05 to 0.95, 0.5 = square)
+    /// Only applies if a voice is selected (Some(n))
+    SetPulseWidth(f32),
+
+    /// Set sub-oscillator level of currently selected voice (0.0 = off, 1.0 = equal to main)
+    /// Only applies if a voice is selected (Some(n))
+    SetSubLevel(f32),
+
+    /// Set sub-oscillator sound source of currently selected voice
+    /// Only applies if a voice is selected (Some(n))
+    SetSubWaveform(Waveform),
+
+    /// Set filter cutoff of currently selected voice (Hz)
+    /// Only applies if a voice is selected (Some(n))
+    SetCutoff(f32),
+
+    /// Set filter resonance of currently selected voice (0.0 to 1.0)
+    /// Only applies if a voice is selected (Some(n))
+    SetResonance(f32),
+
+    /// Set filter key tracking of currently selected voice (0.0 = fixed, 1.0 = follows pitch 1:1)
+    /// Only applies if a voice is selected (Some(n))
+    SetKeyTrack(f32),
+
+    /// Bend the pitch of all voices (-1.0 to 1.0, 0.0 = centered)
+    /// Full deflection spans ±PITCH_BEND_RANGE_SEMITONES
+    PitchBend(f32),
+
+    /// Set whether NoteOn restarts the selected voice's oscillator phase
+    /// true = consistent attacks (default), false = legato
+    SetRetrigger(bool),
+
+    /// Play a MIDI note on a voice picked by the allocator (independent of selection)
+    /// Velocity (1-127) scales the voice's volume; velocity 0 acts as NoteOff
+    NoteOn { note: u8, velocity: u8 },
+
+    /// Release the voice holding a MIDI note (ignored if no voice holds it, e.g. stolen)
+    NoteOff { note: u8 },
+
+    /// Set tremolo LFO rate (Hz, 0.1 to 20.0)
+    SetTremoloRate(f32),
+
+    /// Set tremolo depth (0.0 = off, 1.0 = full)
+    SetTremoloDepth(f32),
+
+    /// Set bitcrusher bit depth (1 to 16, 16 = off)
+    SetBitDepth(u8),
+
+    /// Set bitcrusher sample-hold length (1 to 32, 1 = off)
+    SetDownsample(u8),
+
+    /// Route hard sync: `master` resets `slave`'s phase every cycle (None = sync off)
+    /// Self-sync (master == slave) and out-of-range voices are rejected
+    SetSync { master: Option<u8>, slave: u8 },
+
+    /// Set master volume of the whole mix (0.0 to 1.0, 1.0 on startup)
+    /// Independent of voice selection; smoothed per sample so fades are click-free
+    SetMasterVolume(f32),
+
+    /// Enable or disable the arpeggiator
+    /// While enabled, active voices sound one at a time; disabled, all sound together
+    SetArpEnabled(bool),
+
+    /// Set the order in which the arpeggiator steps through active voices
+    SetArpPattern(ArpPattern),
+
+    /// Set the arpeggiator step length (ms)
+    SetArpStepMs(f32),
+
+    /// Set master stereo width (0.0 = mono, 1.0 = unchanged, up to STEREO_WIDTH_MAX = wider)
+    /// Ignored by mono output formats, and inaudible until voices are panned (the mix is mono)
+    SetWidth(f32),
+
+    /// Enable or bypass the master limiter (enabled on startup)
+    SetLimiter(bool),
+
+    /// Save the current engine state to a preset slot in flash
+    SavePreset(u8),
+
+    /// Restore the engine state from a preset slot in flash
+    /// Empty or invalid slots are ignored
+    LoadPreset(u8),
+
+    /// Copy the table staged in `WavetableStaging` into a user wavetable slot
+    /// Voices playing `Waveform::Custom(slot)` continue from the same phase
+    LoadWavetable(u8),
+}
+
+impl Message {
+    /// Whether this message sets a continuously varying value.
+    ///
+    /// A newer continuous message fully replaces an older one of the same
+    /// kind, so senders may coalesce them (keep only the latest) under load.
+    /// Discrete messages (toggles, notes, presets) must all be delivered.
+    pub fn is_continuous(&self) -> bool {
+        matches!(
+            self,
+            Message::SetFrequency(_)
+                | Message::SetVolume(_)
+                | Message::SetPulseWidth(_)
+                | Message::SetSubLevel(_)
+                | Message::SetCutoff(_)
+                | Message::SetResonance(_)
+                | Message::SetKeyTrack(_)
+                | Message::PitchBend(_)
+                | Message::SetTremoloRate(_)
+                | Message::SetTremoloDepth(_)
+                | Message::SetMasterVolume(_)
+                | Message::SetWidth(_)
+                | Message::SetArpStepMs(_)
+        )
+    }
+}
+
+/// Non-blocking supplier of control messages, drained by the engine on every render.
+pub trait MessageSource {
+    /// Next pending message, or None if nothing is queued.
+    fn try_next(&mut self) -> Option<Message>;
+}
+
+impl<M: RawMutex, const N: usize> MessageSource for Receiver<'_, M, Message, N> {
+    fn try_next(&mut self) -> Option<Message> {
+        self.try_receive().ok()
+    }
+}
+
+/// Fixed-capacity in-memory message queue.
+///
+/// Drives the engine without embassy channels, e.g. from host-side tests
+/// or scripted message sequences.
+pub struct QueueSource<const N: usize> {
+    queue: Deque<Message, N>,
+}
+
+impl<const N: usize> QueueSource<N> {
+    /// Create an empty queue.
+    pub const fn new() -> Self {
+        Self { queue: Deque::new() }
+    }
+
+    /// Append a message.
+    ///
+    /// # Returns
+    /// The message back if the queue is full
+    pub fn push(&mut self, msg: Message) -> Result<(), Message> {
+        self.queue.push_back(msg)
+    }
+}
+
+impl<const N: usize> Default for QueueSource<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<const N: usize> MessageSource for QueueSource<N> {
+    fn try_next(&mut self) -> Option<Message> {
+        self.queue.pop_front()
+    }
 }
@@ -0,0 +1,43 @@
+//! Per-voice output level metering shared between the audio engine and the LED task.
+
+use core::sync::atomic::{AtomicU32, Ordering};
+
+use crate::config::VOICE_COUNT;
+
+/// Latest output level of every voice, shared lock-free between the audio
+/// and LED tasks.
+///
+/// Levels are f32 bits in atomics: the engine stores once per render and the
+/// LED task loads at LED_UPDATE_HZ, so neither side ever waits on the other.
+pub struct VoiceLevels {
+    levels: [AtomicU32; VOICE_COUNT],
+}
+
+impl VoiceLevels {
+    /// All levels at 0.0 (0u32 is the bit pattern of 0.0f32).
+    pub const fn new() -> Self {
+        Self {
+            levels: [const { AtomicU32::new(0) }; VOICE_COUNT],
+        }
+    }
+
+    /// Publish a voice's level (0.0 to 1.0); out-of-range indices are ignored.
+    pub fn store(&self, voice_idx: usize, level: f32) {
+        if let Some(slot) = self.levels.get(voice_idx) {
+            slot.store(level.to_bits(), Ordering::Relaxed);
+        }
+    }
+
+    /// Most recently published level of a voice (0.0 for out-of-range indices).
+    pub fn load(&self, voice_idx: usize) -> f32 {
+        self.levels
+            .get(voice_idx)
+            .map_or(0.0, |slot| f32::from_bits(slot.load(Ordering::Relaxed)))
+    }
+}
+
+impl Default for VoiceLevels {
+    fn default() -> Self {
+        Self::new()
+    }
+}
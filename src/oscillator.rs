@@ -1,6 +1,78 @@
 #![allow(dead_code)]
 
-use crate::config::{WAVETABLE_MASK, WAVETABLE_SIZE_F32};
+use micromath::F32Ext;
+
+use crate::config::{
+    PULSE_WIDTH_MAX, PULSE_WIDTH_MIN, WAVETABLE_BITS, WAVETABLE_MASK, WAVETABLE_SIZE,
+};
+
+/// Low phase bits below the table index, used as the interpolation fraction.
+const PHASE_FRAC_BITS: u32 = u32::BITS - WAVETABLE_BITS;
+
+/// Mask selecting the fractional phase bits.
+const PHASE_FRAC_MASK: u32 = (1 << PHASE_FRAC_BITS) - 1;
+
+/// Scale converting fractional phase bits to a 0.0..1.0 interpolation coefficient.
+const PHASE_FRAC_SCALE: f32 = 1.0 / (1u32 << PHASE_FRAC_BITS) as f32;
+
+/// One full cycle in phase units (2^32) as f32.
+const PHASE_CYCLE_F32: f32 = 4_294_967_296.0;
+
+/// Scale converting a signed 32-bit noise word to -1.0..1.0.
+const NOISE_SCALE: f32 = 1.0 / 2_147_483_648.0;
+
+/// Fallback seed (xorshift32 gets stuck at zero, so zero seeds are replaced).
+const DEFAULT_NOISE_SEED: u32 = 0x2545_F491;
+
+/// Id bit marking a user wavetable slot in `Waveform::to_u8`.
+const CUSTOM_ID_FLAG: u8 = 0x80;
+
+/// Single-cycle wavetable.
+///
+/// Samples should be normalized to ±1.0 and ideally DC-free (zero mean),
+/// since the table is played back as-is.
+pub type Wavetable = [f32; WAVETABLE_SIZE];
+
+/// Sound source produced by an oscillator.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Waveform {
+    /// Interpolated sine wavetable
+    Sine,
+    /// White noise (frequency is ignored)
+    Noise,
+    /// One-pole low-passed noise; frequency sets the filter cutoff
+    PinkNoise,
+    /// Pulse wave; duty cycle set by pulse width (0.5 = square)
+    Square,
+    /// User wavetable slot (see `Message::LoadWavetable`); plays sine if the slot is missing
+    /// Slots start out as copies of SINE until a table is loaded
+    Custom(u8),
+}
+
+impl Waveform {
+    /// Stable numeric id for serialization (presets).
+    pub const fn to_u8(self) -> u8 {
+        match self {
+            Waveform::Sine => 0,
+            Waveform::Noise => 1,
+            Waveform::PinkNoise => 2,
+            Waveform::Square => 3,
+            Waveform::Custom(slot) => CUSTOM_ID_FLAG | slot,
+        }
+    }
+
+    /// Inverse of `to_u8` (None for unknown ids).
+    pub const fn from_u8(id: u8) -> Option<Self> {
+        match id {
+            0 => Some(Waveform::Sine),
+            1 => Some(Waveform::Noise),
+            2 => Some(Waveform::PinkNoise),
+            3 => Some(Waveform::Square),
+            id if id & CUSTOM_ID_FLAG != 0 => Some(Waveform::Custom(id & !CUSTOM_ID_FLAG)),
+            _ => None,
+        }
+    }
+}
 
 /// High-quality 1024-sample sine wave lookup table.
 /// Values are normalized to the range -1.0 to 1.0 for clean DSP processing.
@@ -136,20 +208,39 @@ pub const SINE: [f32; 1024] = [
     -0.049068, -0.042938, -0.036807, -0.030675, -0.024541, -0.018407, -0.012272, -0.006136,
 ];
 
-/// Sine wave oscillator that generates samples from a wavetable.
+/// Oscillator that generates samples from a wavetable or a noise source.
 ///
 /// Outputs normalized f32 samples in the range -1.0 to 1.0.
+///
+/// Phase is a 32-bit fixed-point accumulator covering one cycle: the top
+/// WAVETABLE_BITS bits index the table and the remaining bits are the
+/// interpolation fraction. Wrapping is the natural integer overflow, so the
+/// phase never accumulates rounding drift and periodicity is exact.
 pub struct Oscillator {
-    /// Current position in wavetable index space (0.0 to WAVETABLE_SIZE)
-    /// Stored as index to eliminate multiply in tick()
-    phase: f32,
-    /// Phase advance per sample in wavetable index space (determines frequency)
-    /// Scaled to wavetable size to match phase units
-    phase_increment: f32,
+    /// Current position in the cycle (full u32 range = one cycle)
+    phase: u32,
+    /// Phase advance per sample (determines frequency)
+    phase_increment: u32,
     /// Sample rate in Hz (constant for the lifetime of the oscillator)
     sample_rate: f32,
     /// Reference to the wavetable (normalized f32 values)
     wavetable: &'static [f32],
+    /// Selected sound source
+    waveform: Waveform,
+    /// xorshift32 PRNG state for noise (never zero)
+    noise_state: u32,
+    /// One-pole filter state for pink noise
+    pink_state: f32,
+    /// One-pole filter coefficient for pink noise (derived from frequency)
+    pink_coeff: f32,
+    /// Make-up gain bringing pink noise back to the white-noise level
+    pink_gain: f32,
+    /// Fraction of the cycle the pulse wave is high (clamped)
+    pulse_width: f32,
+    /// Phase below which the pulse wave is high (pulse width × 2^32)
+    pulse_threshold: u32,
+    /// Whether the last tick completed a cycle (phase accumulator overflowed)
+    wrapped: bool,
 }
 
 impl Oscillator {
@@ -159,47 +250,259 @@ impl Oscillator {
     /// * `frequency` - Frequency in Hz (e.g., 440.0 for A4)
     /// * `sample_rate` - Sample rate in Hz (e.g., 44100.0)
     pub fn new(frequency: f32, sample_rate: f32) -> Self {
-        // Scale phase_increment to wavetable index space
-        // This eliminates one multiply per tick()
-        let phase_increment = (frequency / sample_rate) * WAVETABLE_SIZE_F32;
         Self {
-            phase: 0.0,
-            phase_increment,
+            phase: 0,
+            phase_increment: phase_increment(frequency, sample_rate),
             sample_rate,
             wavetable: &SINE,
+            waveform: Waveform::Sine,
+            noise_state: DEFAULT_NOISE_SEED,
+            pink_state: 0.0,
+            pink_coeff: pink_coeff(frequency, sample_rate),
+            pink_gain: pink_gain(pink_coeff(frequency, sample_rate)),
+            pulse_width: 0.5,
+            pulse_threshold: pulse_threshold(0.5),
+            wrapped: false,
         }
     }
 
     /// Change the oscillator's frequency.
     ///
+    /// For white noise this has no audible effect; for pink noise it sets the
+    /// low-pass cutoff.
+    ///
     /// # Arguments
     /// * `frequency` - New frequency in Hz
     pub fn set_frequency(&mut self, frequency: f32) {
-        // Scale to wavetable index space (matches phase units)
-        self.phase_increment = (frequency / self.sample_rate) * WAVETABLE_SIZE_F32;
+        self.phase_increment = phase_increment(frequency, self.sample_rate);
+        self.pink_coeff = pink_coeff(frequency, self.sample_rate);
+        self.pink_gain = pink_gain(self.pink_coeff);
+    }
+
+    /// Restart the cycle from phase zero (sine and square start at their cycle origin).
+    ///
+    /// Only touches this oscillator's phase; costs nothing on the per-sample path.
+    pub fn reset_phase(&mut self) {
+        self.phase = 0;
+    }
+
+    /// Whether the most recent tick completed a cycle (drives hard sync).
+    ///
+    /// Detected from the phase accumulator overflowing, so it fires exactly
+    /// once per cycle at any frequency. Noise waveforms still advance the
+    /// (unheard) phase, so they can act as a sync master too.
+    pub fn wrapped(&self) -> bool {
+        self.wrapped
+    }
+
+    /// Select the sound source.
+    pub fn set_waveform(&mut self, waveform: Waveform) {
+        self.waveform = waveform;
+    }
+
+    /// Currently selected sound source.
+    pub fn waveform(&self) -> Waveform {
+        self.waveform
+    }
+
+    /// Set the fraction of the cycle the square wave spends high.
+    ///
+    /// Clamped to PULSE_WIDTH_MIN..=PULSE_WIDTH_MAX so the output never
+    /// collapses to DC; 0.5 is a plain square.
+    pub fn set_pulse_width(&mut self, width: f32) {
+        self.pulse_width = width.clamp(PULSE_WIDTH_MIN, PULSE_WIDTH_MAX);
+        self.pulse_threshold = pulse_threshold(self.pulse_width);
+    }
+
+    /// Fraction of the cycle the square wave spends high (after clamping).
+    pub fn pulse_width(&self) -> f32 {
+        self.pulse_width
+    }
+
+    /// Seed the noise generator.
+    ///
+    /// Give every oscillator a different seed so noise voices stay uncorrelated.
+    /// A zero seed is replaced by a fixed non-zero one.
+    pub fn set_noise_seed(&mut self, seed: u32) {
+        self.noise_state = if seed == 0 { DEFAULT_NOISE_SEED } else { seed };
     }
 
     /// Generate the next sample.
     ///
     /// Returns a normalized f32 value in the range -1.0 to 1.0.
+    /// `Custom` waveforms play the built-in sine here; use `tick_with` to reach user tables.
     pub fn tick(&mut self) -> f32 {
-        // Advance phase (already in wavetable index space)
-        self.phase += self.phase_increment;
+        match self.waveform {
+            Waveform::Sine | Waveform::Custom(_) => {
+                let table = self.wavetable;
+                self.tick_table(table)
+            }
+            Waveform::Noise => {
+                self.advance_phase();
+                self.tick_noise()
+            }
+            Waveform::PinkNoise => {
+                self.advance_phase();
+                // One-pole low-pass of white noise, scaled back up to the white-noise RMS
+                self.pink_state += self.pink_coeff * (self.tick_noise() - self.pink_state);
+                (self.pink_state * self.pink_gain).clamp(-1.0, 1.0)
+            }
+            Waveform::Square => self.tick_pulse(),
+        }
+    }
 
-        // Wrap phase to [0.0, WAVETABLE_SIZE) range
-        if self.phase >= WAVETABLE_SIZE_F32 {
-            self.phase -= WAVETABLE_SIZE_F32;
+    /// Generate the next sample, reading `Custom` waveforms from `user_tables`.
+    ///
+    /// Switching or reloading a table never touches the phase, so playback
+    /// continues from the same point in the cycle.
+    ///
+    /// # Arguments
+    /// * `user_tables` - User wavetable slots, indexed by `Waveform::Custom(slot)`
+    #[inline(always)]
+    pub fn tick_with(&mut self, user_tables: &[Wavetable]) -> f32 {
+        if let Waveform::Custom(slot) = self.waveform {
+            if let Some(table) = user_tables.get(slot as usize) {
+                return self.tick_table(table);
+            }
         }
+        self.tick()
+    }
 
-        // Phase is already the position - no multiply needed!
-        let index = self.phase as usize;
-        let frac = self.phase - index as f32;
+    /// Advance the phase by one sample, flagging a completed cycle.
+    #[inline(always)]
+    fn advance_phase(&mut self) {
+        // Overflow wraps exactly at the end of the cycle; the sum is smaller than
+        // the old phase only when it wrapped (the increment is below one cycle)
+        let previous = self.phase;
+        self.phase = previous.wrapping_add(self.phase_increment);
+        self.wrapped = self.phase < previous;
+    }
+
+    /// Next interpolated sample from `table`.
+    #[inline(always)]
+    fn tick_table(&mut self, table: &[f32]) -> f32 {
+        self.advance_phase();
+
+        // Top bits select the table entry, low bits give the interpolation fraction
+        let index = (self.phase >> PHASE_FRAC_BITS) as usize;
+        let frac = (self.phase & PHASE_FRAC_MASK) as f32 * PHASE_FRAC_SCALE;
 
         // Use bitwise AND for wrapping (faster than modulo for power-of-2 sizes)
-        let sample1 = self.wavetable[index];
-        let sample2 = self.wavetable[(index + 1) & WAVETABLE_MASK];
+        let sample1 = table[index];
+        let sample2 = table[(index + 1) & WAVETABLE_MASK];
 
         // FMA (fused multiply-add) - single instruction on XTensa LX7
         sample1 + (sample2 - sample1) * frac
     }
+
+    /// Next pulse wave sample (+1.0 while phase is below the width, else -1.0).
+    #[inline(always)]
+    fn tick_pulse(&mut self) -> f32 {
+        self.advance_phase();
+        if self.phase < self.pulse_threshold {
+            1.0
+        } else {
+            -1.0
+        }
+    }
+
+    /// Next white noise sample in -1.0..1.0 (xorshift32).
+    #[inline(always)]
+    fn tick_noise(&mut self) -> f32 {
+        let mut x = self.noise_state;
+        x ^= x << 13;
+        x ^= x >> 17;
+        x ^= x << 5;
+        self.noise_state = x;
+        (x as i32) as f32 * NOISE_SCALE
+    }
+}
+
+/// Convert a frequency to a fixed-point phase increment (rounded to nearest).
+///
+/// Float → int casts saturate, so negative frequencies map to 0 (DC)
+/// rather than wrapping into a huge increment.
+fn phase_increment(frequency: f32, sample_rate: f32) -> u32 {
+    ((frequency / sample_rate) * PHASE_CYCLE_F32 + 0.5) as u32
+}
+
+/// Convert a pulse width (fraction of the cycle) to a phase threshold.
+fn pulse_threshold(width: f32) -> u32 {
+    (width.clamp(PULSE_WIDTH_MIN, PULSE_WIDTH_MAX) * PHASE_CYCLE_F32) as u32
+}
+
+/// One-pole low-pass coefficient for a cutoff frequency.
+///
+/// Uses the small-angle approximation `2π·fc/fs` (avoids exp), clamped to
+/// 0.0..1.0 so the filter stays stable at any frequency.
+fn pink_coeff(frequency: f32, sample_rate: f32) -> f32 {
+    (core::f32::consts::TAU * frequency / sample_rate).clamp(0.0, 1.0)
+}
+
+/// Gain restoring the RMS a one-pole low-pass removes from white noise.
+///
+/// The filter passes `c / (2 - c)` of the input's power for coefficient `c`;
+/// the floor keeps a 0 Hz cutoff finite (its state never leaves zero anyway).
+fn pink_gain(coeff: f32) -> f32 {
+    let coeff = coeff.max(1e-6);
+    ((2.0 - coeff) / coeff).sqrt()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE_RATE: f32 = 44_100.0;
+
+    #[test]
+    fn phase_never_drifts_from_the_exact_integer_phase() {
+        // One minute of A4: the accumulator must equal n × increment mod 2^32 exactly
+        let mut osc = Oscillator::new(440.0, SAMPLE_RATE);
+        let increment = osc.phase_increment as u64;
+        let ticks = 60 * SAMPLE_RATE as u64;
+
+        let mut cycles = 0u64;
+        for _ in 0..ticks {
+            osc.tick();
+            cycles += osc.wrapped() as u64;
+        }
+
+        let exact = ticks * increment;
+        assert_eq!(osc.phase as u64, exact % (1u64 << 32));
+        assert_eq!(cycles, exact >> 32);
+    }
+
+    #[test]
+    fn cycles_repeat_bit_for_bit() {
+        // fs / 64 gives an increment of exactly 2^26, so every 64th sample restarts the cycle
+        let mut osc = Oscillator::new(SAMPLE_RATE / 64.0, SAMPLE_RATE);
+        assert_eq!(osc.phase_increment, 1 << 26);
+
+        let first: [f32; 64] = core::array::from_fn(|_| osc.tick());
+        for _ in 0..100_000 {
+            let cycle: [f32; 64] = core::array::from_fn(|_| osc.tick());
+            assert_eq!(cycle, first);
+        }
+        assert_eq!(osc.phase, 0);
+    }
+
+    #[test]
+    fn pink_noise_plays_at_the_white_noise_level() {
+        let rms = |waveform| {
+            let mut osc = Oscillator::new(500.0, SAMPLE_RATE);
+            osc.set_waveform(waveform);
+            let ticks = SAMPLE_RATE as usize;
+            let energy: f32 = (0..ticks)
+                .map(|_| {
+                    let sample = osc.tick();
+                    sample * sample
+                })
+                .sum();
+            (energy / ticks as f32).sqrt()
+        };
+
+        let white = rms(Waveform::Noise);
+        let pink = rms(Waveform::PinkNoise);
+        assert!((pink / white - 1.0).abs() < 0.1, "pink {pink} vs white {white}");
+    }
 }
@@ -0,0 +1,60 @@
+//! Pitch helpers: MIDI note and semitone conversions without libm.
+
+use crate::config::TUNING_A4_HZ;
+use micromath::F32Ext;
+
+/// MIDI note number of A4.
+const NOTE_A4: i32 = 69;
+
+/// Frequency ratios for 0–11 semitones above a reference (2^(n/12)).
+#[allow(clippy::approx_constant)]
+pub const SEMITONE_RATIOS: [f32; 12] = [
+    1.0, 1.0594631, 1.122462, 1.1892071, 1.259921, 1.3348399,
+    1.4142136, 1.4983071, 1.5874011, 1.6817928, 1.7817974, 1.8877486,
+];
+
+/// Exact 2^octaves for an integer octave count (valid for -126..=127).
+///
+/// Builds the f32 directly from its exponent bits instead of calling powf.
+#[inline(always)]
+pub fn octave_ratio(octaves: i32) -> f32 {
+    f32::from_bits(((127 + octaves) as u32) << 23)
+}
+
+/// Frequency ratio for a whole number of semitones (positive or negative).
+pub fn whole_semitone_ratio(semitones: i32) -> f32 {
+    SEMITONE_RATIOS[semitones.rem_euclid(12) as usize] * octave_ratio(semitones.div_euclid(12))
+}
+
+/// Frequency ratio for a fractional number of semitones (2^(semitones/12)).
+///
+/// The whole part comes from the semitone table; the remaining fraction of a
+/// semitone uses a cubic Taylor series of e^x (error < 1e-6), so 0.0 returns
+/// exactly 1.0.
+pub fn semitone_ratio(semitones: f32) -> f32 {
+    // Floor without libm: truncate, then step down for negative fractions
+    let mut whole = semitones as i32;
+    if whole as f32 > semitones {
+        whole -= 1;
+    }
+    let x = (semitones - whole as f32) * (core::f32::consts::LN_2 / 12.0);
+    let fraction_ratio = 1.0 + x * (1.0 + x * (0.5 + x * (1.0 / 6.0)));
+
+    whole_semitone_ratio(whole) * fraction_ratio
+}
+
+/// Interval in semitones spanned by a frequency ratio (12 · log2(ratio)).
+///
+/// Inverse of `semitone_ratio`; uses micromath's log2 approximation, which is
+/// plenty for control-rate uses like key tracking. Non-positive ratios return 0.0.
+pub fn ratio_to_semitones(ratio: f32) -> f32 {
+    if ratio <= 0.0 {
+        return 0.0;
+    }
+    12.0 * ratio.log2()
+}
+
+/// Equal-tempered frequency of a MIDI note number (A4 = 69 = TUNING_A4_HZ).
+pub fn note_to_frequency(note: u8) -> f32 {
+    TUNING_A4_HZ * whole_semitone_ratio(note as i32 - NOTE_A4)
+}
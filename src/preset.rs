@@ -0,0 +1,570 @@
+//! Preset snapshots of the engine state, the learned pot calibration, and
+//! their storage in flash.
+//!
+//! Serialization is a fixed little-endian byte layout (no allocation, no
+//! reliance on in-memory struct layout), guarded by a magic word so erased
+//! or foreign flash contents are rejected instead of restored.
+//!
+//! Flash is only touched by `preset_task` (and once at boot, to read the
+//! calibration). The engine hands it snapshots and load requests through
+//! `PresetRequests` and picks loaded states up from `LoadedPreset`. A write
+//! stalls the CPU for a sector erase, far longer than the DMA ring lasts, so
+//! every write first has the engine fade to silence through `FlashMute`: the
+//! ring replays silence during the stall instead of stale audio.
+
+use core::sync::atomic::{AtomicBool, Ordering};
+
+use crate::config::{
+    POT_CALIBRATION_MIN_SPAN, POT_COUNT, POT_MAX, POT_MIN, PRESET_REQUEST_QUEUE_SIZE,
+    PRESET_SLOT_SIZE, PULSE_WIDTH_MAX, PULSE_WIDTH_MIN, VOICE_COUNT,
+};
+use crate::oscillator::Waveform;
+use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+use embassy_sync::channel::Channel;
+use embassy_sync::signal::Signal;
+
+// Flash access is device-only; snapshots and requests also build on the host
+#[cfg(target_arch = "xtensa")]
+use crate::config::{CALIBRATION_FLASH_OFFSET, PRESET_FLASH_OFFSET, PRESET_SLOT_COUNT};
+#[cfg(target_arch = "xtensa")]
+use embedded_storage::{ReadStorage, Storage};
+#[cfg(target_arch = "xtensa")]
+use esp_storage::{FlashStorage, FlashStorageError};
+#[cfg(target_arch = "xtensa")]
+use log::warn;
+
+/// Magic word marking a valid preset (bump the last byte when the layout changes).
+const PRESET_MAGIC: [u8; 4] = *b"SYP3";
+
+/// Magic word marking a valid pot calibration record.
+const CALIBRATION_MAGIC: [u8; 4] = *b"SYC1";
+
+/// Serialized size of one pot range: min (f32) + max (f32).
+const POT_CALIBRATION_SIZE: usize = 8;
+
+/// Serialized size of one voice: seven f32 parameters (frequency, volume, pulse
+/// width, sub level, cutoff, resonance, key track) + waveform (u8) + sub
+/// waveform (u8) + flags (u8).
+const VOICE_STATE_SIZE: usize = 7 * 4 + 3;
+
+/// Flag bit set when the voice is active.
+const FLAG_ACTIVE: u8 = 1 << 0;
+
+/// Flag bit set when note_on restarts the voice's phase.
+const FLAG_RETRIGGER: u8 = 1 << 1;
+
+/// Stored `selected_voice` value meaning "no selection".
+const NO_SELECTION: u8 = u8::MAX;
+
+/// Persistable state of a single voice.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct VoiceState {
+    /// Base frequency in Hz
+    pub frequency: f32,
+    /// Target volume (0.0 to 1.0)
+    pub volume: f32,
+    /// Oscillator sound source
+    pub waveform: Waveform,
+    /// Fraction of the cycle the square wave spends high
+    pub pulse_width: f32,
+    /// Sub-oscillator mix level (0.0 = off)
+    pub sub_level: f32,
+    /// Sub-oscillator sound source
+    pub sub_waveform: Waveform,
+    /// Filter cutoff in Hz (at KEY_TRACK_REFERENCE_HZ when key tracking)
+    pub cutoff: f32,
+    /// Filter resonance (0.0 to 1.0)
+    pub resonance: f32,
+    /// Filter key tracking (0.0 to 1.0)
+    pub key_track: f32,
+    /// Whether note_on restarts the phase
+    pub retrigger: bool,
+    /// Whether the voice is switched on
+    pub active: bool,
+}
+
+impl VoiceState {
+    /// Serialize into one voice's chunk of the preset layout.
+    fn write(&self, chunk: &mut [u8]) {
+        let params = [
+            self.frequency,
+            self.volume,
+            self.pulse_width,
+            self.sub_level,
+            self.cutoff,
+            self.resonance,
+            self.key_track,
+        ];
+        for (bytes, param) in chunk.chunks_exact_mut(4).zip(params) {
+            bytes.copy_from_slice(&param.to_le_bytes());
+        }
+        chunk[28] = self.waveform.to_u8();
+        chunk[29] = self.sub_waveform.to_u8();
+        chunk[30] = if self.active { FLAG_ACTIVE } else { 0 }
+            | if self.retrigger { FLAG_RETRIGGER } else { 0 };
+    }
+
+    /// Deserialize one voice's chunk of the preset layout.
+    ///
+    /// # Returns
+    /// None if any waveform is unknown or any parameter is out of its range
+    fn read(chunk: &[u8]) -> Option<Self> {
+        let param = |idx: usize| {
+            let bytes = &chunk[idx * 4..idx * 4 + 4];
+            f32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]])
+        };
+        let unit = |value: f32| (0.0..=1.0).contains(&value).then_some(value);
+
+        let frequency = param(0);
+        let cutoff = param(4);
+        let pulse_width = param(2);
+        if !frequency.is_finite() || !cutoff.is_finite() || cutoff <= 0.0 {
+            return None;
+        }
+        if !(PULSE_WIDTH_MIN..=PULSE_WIDTH_MAX).contains(&pulse_width) {
+            return None;
+        }
+
+        Some(Self {
+            frequency,
+            volume: unit(param(1))?,
+            waveform: Waveform::from_u8(chunk[28])?,
+            pulse_width,
+            sub_level: unit(param(3))?,
+            sub_waveform: Waveform::from_u8(chunk[29])?,
+            cutoff,
+            resonance: unit(param(5))?,
+            key_track: unit(param(6))?,
+            retrigger: chunk[30] & FLAG_RETRIGGER != 0,
+            active: chunk[30] & FLAG_ACTIVE != 0,
+        })
+    }
+}
+
+/// Persistable snapshot of the whole engine.
+///
+/// Only user-facing parameters are stored; cached values derived from them
+/// (mix weight, normalization reciprocal) are recomputed on restore.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct EngineState {
+    /// Per-voice parameters
+    pub voices: [VoiceState; VOICE_COUNT],
+    /// Voice controlled by pots/encoders (None = no selection)
+    pub selected_voice: Option<u8>,
+}
+
+impl EngineState {
+    /// Serialized size in bytes.
+    pub const SIZE: usize = PRESET_MAGIC.len() + VOICE_COUNT * VOICE_STATE_SIZE + 1;
+
+    /// Serialize into a fixed-size little-endian byte array.
+    pub fn to_bytes(&self) -> [u8; Self::SIZE] {
+        let mut bytes = [0u8; Self::SIZE];
+        bytes[..PRESET_MAGIC.len()].copy_from_slice(&PRESET_MAGIC);
+
+        let voice_bytes = &mut bytes[PRESET_MAGIC.len()..Self::SIZE - 1];
+        for (chunk, voice) in voice_bytes.chunks_exact_mut(VOICE_STATE_SIZE).zip(&self.voices) {
+            voice.write(chunk);
+        }
+
+        bytes[Self::SIZE - 1] = self.selected_voice.unwrap_or(NO_SELECTION);
+        bytes
+    }
+
+    /// Deserialize from bytes produced by `to_bytes`.
+    ///
+    /// Only the first `SIZE` bytes are read, so a whole flash slot can be passed.
+    ///
+    /// # Returns
+    /// None if `bytes` is shorter than `SIZE`, the magic word is missing
+    /// (e.g. erased flash or an older layout) or any value is invalid
+    pub fn from_bytes(bytes: &[u8]) -> Option<Self> {
+        let bytes = bytes.get(..Self::SIZE)?;
+        if bytes[..PRESET_MAGIC.len()] != PRESET_MAGIC {
+            return None;
+        }
+
+        let mut voices = [None; VOICE_COUNT];
+        let voice_bytes = &bytes[PRESET_MAGIC.len()..Self::SIZE - 1];
+        for (voice, chunk) in voices.iter_mut().zip(voice_bytes.chunks_exact(VOICE_STATE_SIZE)) {
+            *voice = VoiceState::read(chunk);
+        }
+        if voices.contains(&None) {
+            return None;
+        }
+        let voices = voices.map(Option::unwrap);
+
+        let selected_voice = match bytes[Self::SIZE - 1] {
+            NO_SELECTION => None,
+            idx if (idx as usize) < VOICE_COUNT => Some(idx),
+            _ => return None,
+        };
+
+        Some(Self { voices, selected_voice })
+    }
+}
+
+const _: () = assert!(
+    EngineState::SIZE <= PRESET_SLOT_SIZE as usize,
+    "EngineState does not fit in a preset slot"
+);
+
+/// Learned electrical range of a potentiometer in millivolts.
+///
+/// Plain `Copy` data so it can be stored alongside presets.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PotCalibration {
+    /// Reading that maps to 0.0
+    pub min: f32,
+    /// Reading that maps to 1.0
+    pub max: f32,
+}
+
+impl PotCalibration {
+    /// Static range from POT_MIN/POT_MAX (used until a calibration is learned).
+    pub const DEFAULT: Self = Self {
+        min: POT_MIN as f32,
+        max: POT_MAX as f32,
+    };
+}
+
+/// Persistable calibration of every pot, kept in its own flash record.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CalibrationState {
+    /// Per-pot ranges, in polling order (see POT_COUNT)
+    pub pots: [PotCalibration; POT_COUNT],
+}
+
+impl CalibrationState {
+    /// Serialized size in bytes.
+    pub const SIZE: usize = CALIBRATION_MAGIC.len() + POT_COUNT * POT_CALIBRATION_SIZE;
+
+    /// Every pot on the static POT_MIN/POT_MAX range (nothing learned yet).
+    pub const DEFAULT: Self = Self {
+        pots: [PotCalibration::DEFAULT; POT_COUNT],
+    };
+
+    /// Serialize into a fixed-size little-endian byte array.
+    pub fn to_bytes(&self) -> [u8; Self::SIZE] {
+        let mut bytes = [0u8; Self::SIZE];
+        bytes[..CALIBRATION_MAGIC.len()].copy_from_slice(&CALIBRATION_MAGIC);
+
+        let pot_bytes = &mut bytes[CALIBRATION_MAGIC.len()..];
+        for (chunk, pot) in pot_bytes.chunks_exact_mut(POT_CALIBRATION_SIZE).zip(&self.pots) {
+            chunk[0..4].copy_from_slice(&pot.min.to_le_bytes());
+            chunk[4..8].copy_from_slice(&pot.max.to_le_bytes());
+        }
+        bytes
+    }
+
+    /// Deserialize from bytes produced by `to_bytes`.
+    ///
+    /// # Returns
+    /// None if the magic word is missing or any range is narrower than
+    /// POT_CALIBRATION_MIN_SPAN (calibration would never have adopted it)
+    pub fn from_bytes(bytes: &[u8; Self::SIZE]) -> Option<Self> {
+        if bytes[..CALIBRATION_MAGIC.len()] != CALIBRATION_MAGIC {
+            return None;
+        }
+
+        let mut pots = [PotCalibration::DEFAULT; POT_COUNT];
+        let pot_bytes = &bytes[CALIBRATION_MAGIC.len()..];
+        for (pot, chunk) in pots.iter_mut().zip(pot_bytes.chunks_exact(POT_CALIBRATION_SIZE)) {
+            let min = f32::from_le_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]);
+            let max = f32::from_le_bytes([chunk[4], chunk[5], chunk[6], chunk[7]]);
+            if !min.is_finite() || !max.is_finite() || max - min < POT_CALIBRATION_MIN_SPAN {
+                return None;
+            }
+            *pot = PotCalibration { min, max };
+        }
+
+        Some(Self { pots })
+    }
+}
+
+const _: () = assert!(
+    CalibrationState::SIZE <= PRESET_SLOT_SIZE as usize,
+    "CalibrationState does not fit in its flash record"
+);
+
+/// Flash operation requested by the engine or the pot task and carried out by `preset_task`.
+#[derive(Debug, Clone, Copy)]
+pub enum PresetRequest {
+    /// Write a snapshot into a slot
+    Save { slot: u8, state: EngineState },
+    /// Read a slot; a valid snapshot is delivered through `LoadedPreset`
+    Load(u8),
+    /// Write a freshly learned pot calibration (read back at the next boot)
+    SaveCalibration(CalibrationState),
+}
+
+/// Requests from the engine and the pot task to the preset task.
+pub type PresetRequests = Channel<CriticalSectionRawMutex, PresetRequest, PRESET_REQUEST_QUEUE_SIZE>;
+
+/// Snapshot read by the preset task, applied by the engine on its next render.
+pub type LoadedPreset = Signal<CriticalSectionRawMutex, EngineState>;
+
+/// Handshake that silences the engine around flash writes.
+///
+/// The preset task runs on the same executor as the render loop, and a
+/// sector erase blocks the CPU for longer than the DMA ring holds audio, so
+/// the ring is replayed while the write runs. `mute` has the engine fade out
+/// over FADE_MS and fill the whole ring with silence before it returns, so the
+/// replay is inaudible; `unmute` lets the engine fade back in.
+pub struct FlashMute {
+    /// Set by the preset task while a write is pending or running
+    requested: AtomicBool,
+    /// Raised by the engine once the DMA ring holds nothing but silence
+    silent: Signal<CriticalSectionRawMutex, ()>,
+}
+
+impl FlashMute {
+    /// Handshake with no write pending (the engine plays normally).
+    pub const fn new() -> Self {
+        Self {
+            requested: AtomicBool::new(false),
+            silent: Signal::new(),
+        }
+    }
+
+    /// Ask the engine to go silent and wait until the DMA ring holds only silence.
+    pub async fn mute(&self) {
+        self.silent.reset();
+        self.requested.store(true, Ordering::Release);
+        self.silent.wait().await;
+    }
+
+    /// Let the engine fade back in after a write.
+    pub fn unmute(&self) {
+        self.requested.store(false, Ordering::Release);
+    }
+
+    /// Whether a write is waiting for (or holding) silence.
+    pub(crate) fn requested(&self) -> bool {
+        self.requested.load(Ordering::Acquire)
+    }
+
+    /// Report that the DMA ring has been filled with silence.
+    pub(crate) fn confirm_silent(&self) {
+        self.silent.signal(());
+    }
+}
+
+impl Default for FlashMute {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Preset slots stored in a reserved flash region.
+///
+/// Writes go through `FlashStorage`'s read-modify-write of the containing
+/// sector, so saving one slot preserves the others. Flash writes stall the
+/// CPU for tens of milliseconds; only `preset_task` should drive the store.
+#[cfg(target_arch = "xtensa")]
+pub struct PresetStore {
+    flash: FlashStorage,
+}
+
+#[cfg(target_arch = "xtensa")]
+impl PresetStore {
+    /// Create a preset store backed by the on-chip flash.
+    pub fn new(flash: FlashStorage) -> Self {
+        Self { flash }
+    }
+
+    /// Write a snapshot into `slot`.
+    ///
+    /// Out-of-range slots are ignored with a warning.
+    pub fn save(&mut self, slot: u8, state: &EngineState) -> Result<(), FlashStorageError> {
+        let Some(offset) = slot_offset(slot) else {
+            warn!("Preset slot {} out of range", slot);
+            return Ok(());
+        };
+        self.flash.write(offset, &state.to_bytes())
+    }
+
+    /// Read the snapshot stored in `slot`.
+    ///
+    /// # Returns
+    /// None if the slot is out of range, empty, corrupt, or the read failed
+    pub fn load(&mut self, slot: u8) -> Option<EngineState> {
+        let Some(offset) = slot_offset(slot) else {
+            warn!("Preset slot {} out of range", slot);
+            return None;
+        };
+
+        let mut bytes = [0u8; EngineState::SIZE];
+        if let Err(e) = self.flash.read(offset, &mut bytes) {
+            warn!("Preset read failed: {:?}", e);
+            return None;
+        }
+        EngineState::from_bytes(&bytes)
+    }
+
+    /// Write the pot calibration record.
+    pub fn save_calibration(
+        &mut self,
+        calibration: &CalibrationState,
+    ) -> Result<(), FlashStorageError> {
+        self.flash.write(CALIBRATION_FLASH_OFFSET, &calibration.to_bytes())
+    }
+
+    /// Read the pot calibration record.
+    ///
+    /// # Returns
+    /// None if nothing was calibrated yet, the record is corrupt, or the read failed
+    pub fn load_calibration(&mut self) -> Option<CalibrationState> {
+        let mut bytes = [0u8; CalibrationState::SIZE];
+        if let Err(e) = self.flash.read(CALIBRATION_FLASH_OFFSET, &mut bytes) {
+            warn!("Calibration read failed: {:?}", e);
+            return None;
+        }
+        CalibrationState::from_bytes(&bytes)
+    }
+}
+
+/// Preset task: performs the flash I/O requested by the engine and the pot task.
+///
+/// Runs outside render(), so the engine only ever queues a request or takes
+/// a finished snapshot. Writes still stall the whole CPU, render loop
+/// included, so each one is wrapped in a `FlashMute` fade: saving drops the
+/// output to silence for the length of the write. Reads are short enough to
+/// run unmuted. Loads that find an empty or invalid slot are dropped
+/// (nothing is signaled).
+///
+/// # Arguments
+/// * `store` - Flash-backed preset slots (owned by this task)
+/// * `requests` - Save/load requests queued by the engine (and calibration saves)
+/// * `loaded` - Receives each successfully read snapshot
+/// * `mute` - Handshake with the engine (see `Engine::set_preset_channels`)
+#[cfg(target_arch = "xtensa")]
+#[embassy_executor::task]
+pub async fn preset_task(
+    mut store: PresetStore,
+    requests: &'static PresetRequests,
+    loaded: &'static LoadedPreset,
+    mute: &'static FlashMute,
+) {
+    loop {
+        match requests.receive().await {
+            PresetRequest::Save { slot, state } => {
+                mute.mute().await;
+                let result = store.save(slot, &state);
+                mute.unmute();
+                if let Err(e) = result {
+                    warn!("Preset save failed: {:?}", e);
+                }
+            }
+            PresetRequest::Load(slot) => {
+                if let Some(state) = store.load(slot) {
+                    loaded.signal(state);
+                }
+            }
+            PresetRequest::SaveCalibration(calibration) => {
+                mute.mute().await;
+                let result = store.save_calibration(&calibration);
+                mute.unmute();
+                if let Err(e) = result {
+                    warn!("Calibration save failed: {:?}", e);
+                }
+            }
+        }
+    }
+}
+
+/// Flash offset of a preset slot, if the slot exists.
+#[cfg(target_arch = "xtensa")]
+fn slot_offset(slot: u8) -> Option<u32> {
+    (slot < PRESET_SLOT_COUNT).then(|| PRESET_FLASH_OFFSET + slot as u32 * PRESET_SLOT_SIZE)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Snapshot with every field away from its startup value.
+    fn edited_state() -> EngineState {
+        let voice = |i: usize| VoiceState {
+            frequency: 110.0 * (i + 1) as f32,
+            volume: 0.25 * i as f32,
+            waveform: [Waveform::Square, Waveform::PinkNoise, Waveform::Custom(1)][i],
+            pulse_width: 0.2,
+            sub_level: 0.6,
+            sub_waveform: Waveform::Sine,
+            cutoff: 1200.0,
+            resonance: 0.4,
+            key_track: 0.5,
+            retrigger: i == 1,
+            active: i != 1,
+        };
+        EngineState {
+            voices: core::array::from_fn(voice),
+            selected_voice: Some(2),
+        }
+    }
+
+    #[test]
+    fn engine_state_round_trips() {
+        let state = edited_state();
+        assert_eq!(EngineState::from_bytes(&state.to_bytes()), Some(state));
+
+        // A whole flash slot (state plus erased padding) reads back the same
+        let mut slot = [0xFF; PRESET_SLOT_SIZE as usize];
+        slot[..EngineState::SIZE].copy_from_slice(&state.to_bytes());
+        assert_eq!(EngineState::from_bytes(&slot), Some(state));
+
+        let unselected = EngineState { selected_voice: None, ..state };
+        assert_eq!(EngineState::from_bytes(&unselected.to_bytes()), Some(unselected));
+    }
+
+    #[test]
+    fn engine_state_rejects_bad_magic_and_truncated_data() {
+        let bytes = edited_state().to_bytes();
+
+        // Erased flash reads back as all ones
+        assert_eq!(EngineState::from_bytes(&[0xFF; EngineState::SIZE]), None);
+
+        // Older layout (or any other magic word)
+        let mut old = bytes;
+        old[..PRESET_MAGIC.len()].copy_from_slice(b"SYP2");
+        assert_eq!(EngineState::from_bytes(&old), None);
+
+        for len in [0, PRESET_MAGIC.len(), EngineState::SIZE - 1] {
+            assert_eq!(EngineState::from_bytes(&bytes[..len]), None, "{len} bytes");
+        }
+
+        let mut loud = edited_state();
+        loud.voices[2].volume = 1.5;
+        assert_eq!(EngineState::from_bytes(&loud.to_bytes()), None);
+
+        let mut stray = edited_state();
+        stray.selected_voice = Some(VOICE_COUNT as u8);
+        assert_eq!(EngineState::from_bytes(&stray.to_bytes()), None);
+    }
+
+    #[test]
+    fn calibration_round_trips() {
+        let calibration = CalibrationState {
+            pots: [
+                PotCalibration { min: 142.0, max: 3010.5 },
+                PotCalibration { min: 20.0, max: 2900.0 },
+            ],
+        };
+        assert_eq!(CalibrationState::from_bytes(&calibration.to_bytes()), Some(calibration));
+    }
+
+    #[test]
+    fn calibration_rejects_erased_and_invalid_records() {
+        // Erased flash reads back as all ones
+        assert_eq!(CalibrationState::from_bytes(&[0xFF; CalibrationState::SIZE]), None);
+
+        let mut narrow = CalibrationState::DEFAULT;
+        narrow.pots[1] = PotCalibration { min: 1000.0, max: 1000.0 + POT_CALIBRATION_MIN_SPAN / 2.0 };
+        assert_eq!(CalibrationState::from_bytes(&narrow.to_bytes()), None);
+
+        let mut nan = CalibrationState::DEFAULT;
+        nan.pots[0].max = f32::NAN;
+        assert_eq!(CalibrationState::from_bytes(&nan.to_bytes()), None);
+    }
+}
@@ -1,6 +1,25 @@
 //! Voice module: instrument instance with oscillator, volume, and active state.
+//!
+//! A voice holds synthesis state only. Buttons and LEDs belong to the
+//! control tasks in `controls`, which reach voices through messages
+//! (input) and `VoiceLevels` (LED output), so a voice never owns a peripheral.
 
-use crate::{config::VOLUME_SMOOTHING_COEFF, oscillator::Oscillator};
+use crate::{
+    config::{
+        FADE_MS, FILTER_CUTOFF_MAX, KEY_TRACK_REFERENCE_HZ, VELOCITY_MIN_GAIN,
+        VOLUME_SMOOTHING_COEFF,
+    },
+    filter::Filter,
+    oscillator::{Oscillator, Waveform, Wavetable},
+    pitch::{ratio_to_semitones, semitone_ratio},
+};
+
+/// Highest MIDI velocity (maps to the voice's full volume).
+const VELOCITY_MAX: u8 = 127;
+
+/// Distance at which a smoothed sub-mix gain lands exactly on its target.
+/// Ends the glide, so a sub switched off costs nothing and leaves the main oscillator untouched.
+const SUB_GAIN_SNAP: f32 = 1e-4;
 
 /// A single voice in the synth.
 /// Wraps an oscillator with volume control and active state.
@@ -8,15 +27,77 @@ pub struct Voice {
     /// Wavetable oscillator for audio generation
     osc: Oscillator,
 
+    /// Sub-oscillator, always one octave (half the frequency) below `osc`
+    sub_osc: Oscillator,
+
+    /// Sub-oscillator mix level (0.0 = off, 1.0 = as loud as the main oscillator)
+    sub_level: f32,
+
+    /// Target main oscillator gain, 1 / (1 + sub_level), keeps the main + sub mix within ±1.0
+    main_gain_target: f32,
+
+    /// Target sub-oscillator gain, sub_level / (1 + sub_level)
+    sub_gain_target: f32,
+
+    /// Current smoothed main oscillator gain (interpolated toward its target)
+    main_gain_current: f32,
+
+    /// Current smoothed sub-oscillator gain (interpolated toward its target)
+    sub_gain_current: f32,
+
+    /// Resonant low-pass on the oscillator mix (bypassed while fully open)
+    filter: Filter,
+
+    /// Filter cutoff set by the user (Hz), before key tracking
+    cutoff: f32,
+
+    /// How far the cutoff follows the playing frequency (0.0 = fixed, 1.0 = 1:1 with pitch)
+    key_track: f32,
+
+    /// Base frequency in Hz (kept so the voice state can be read back)
+    frequency: f32,
+
+    /// Read-time pitch multiplier (pitch bend), applied on top of `frequency`
+    /// The oscillator always runs at frequency × pitch_ratio, so no drift accumulates
+    pitch_ratio: f32,
+
     /// Target volume set by user (0.0 = silent, 1.0 = full scale)
     volume_target: f32,
 
-    /// Current smoothed volume (interpolated toward target)
+    /// Amplitude scale from the last NoteOn velocity (1.0 = full volume)
+    /// Multiplies volume_target, so the volume control keeps working
+    velocity_gain: f32,
+
+    /// Current smoothed volume (interpolated toward target × velocity)
     /// Updated each tick() to eliminate zipper noise
     volume_current: f32,
 
+    /// MIDI note currently held by this voice (None = not note-driven)
+    note: Option<u8>,
+
+    /// Note that stole this voice, as (frequency, velocity), waiting for the
+    /// previous note to fade out before it starts
+    pending: Option<(f32, u8)>,
+
+    /// Restart the oscillator phase on every note_on (false = legato, phase continues)
+    retrigger: bool,
+
+    /// Whether the voice is let through by the arpeggiator (true when it's off)
+    /// A closed gate ramps the fade to zero just like switching the voice off
+    gate: bool,
+
+    /// Largest output magnitude since the last take_peak() (drives the voice LED)
+    peak: f32,
+
+    /// Anti-pop ramp gain (0.0 = silent, 1.0 = full), moving toward 1.0 while
+    /// active with the gate open and 0.0 otherwise by `fade_step` per sample
+    fade: f32,
+
+    /// Per-sample ramp increment (FADE_MS for a full 0 → 1 ramp)
+    fade_step: f32,
+
     /// Whether voice is active (on) or inactive (off)
-    /// When inactive, tick() returns 0.0 regardless of volume
+    /// After switching off the voice keeps sounding until its fade-out completes
     pub active: bool,
 }
 
@@ -26,22 +107,165 @@ impl Voice {
     /// # Arguments
     /// * `frequency` - Initial frequency in Hz
     /// * `sample_rate` - Audio sample rate in Hz
+    /// * `noise_seed` - Noise generator seed (use a different one per voice)
     ///
     /// # Returns
-    /// Voice with specified frequency, DEFAULT_VOLUME, inactive state
-    pub fn new(frequency: f32, sample_rate: f32) -> Self {
+    /// Voice with specified frequency, DEFAULT_VOICE_VOLUME, sine waveform, inactive state
+    pub fn new(frequency: f32, sample_rate: f32, noise_seed: u32) -> Self {
         let default_vol = crate::config::DEFAULT_VOICE_VOLUME;
+        let mut osc = Oscillator::new(frequency, sample_rate);
+        osc.set_noise_seed(noise_seed);
+        let mut sub_osc = Oscillator::new(frequency * 0.5, sample_rate);
+        sub_osc.set_waveform(Waveform::Square);
+        sub_osc.set_noise_seed(!noise_seed);
         Self {
-            osc: Oscillator::new(frequency, sample_rate),
+            osc,
+            sub_osc,
+            sub_level: 0.0,
+            main_gain_target: 1.0,
+            sub_gain_target: 0.0,
+            main_gain_current: 1.0,
+            sub_gain_current: 0.0,
+            filter: Filter::new(sample_rate),
+            cutoff: FILTER_CUTOFF_MAX,
+            key_track: 0.0,
+            frequency,
+            pitch_ratio: 1.0,
             volume_target: default_vol,
+            velocity_gain: 1.0,
             volume_current: default_vol,
+            note: None,
+            pending: None,
+            retrigger: true,
+            gate: true,
+            peak: 0.0,
+            fade: 0.0,
+            fade_step: 1000.0 / (FADE_MS * sample_rate),
             active: false,
         }
     }
 
     /// Set voice frequency in Hz.
     pub fn set_frequency(&mut self, freq: f32) {
+        self.frequency = freq;
+        self.update_osc_frequency();
+    }
+
+    /// Set the pitch multiplier applied on top of the base frequency (1.0 = unchanged).
+    pub fn set_pitch_ratio(&mut self, ratio: f32) {
+        self.pitch_ratio = ratio;
+        self.update_osc_frequency();
+    }
+
+    /// Retune both oscillators to frequency × pitch_ratio (sub at exactly half).
+    fn update_osc_frequency(&mut self) {
+        let freq = self.frequency * self.pitch_ratio;
         self.osc.set_frequency(freq);
+        self.sub_osc.set_frequency(freq * 0.5);
+        self.update_filter_cutoff();
+    }
+
+    /// Apply key tracking: cutoff × (playing frequency / KEY_TRACK_REFERENCE_HZ)^key_track.
+    ///
+    /// Recomputed only when pitch, cutoff or tracking change, never per sample.
+    fn update_filter_cutoff(&mut self) {
+        let cutoff = if self.key_track == 0.0 {
+            self.cutoff
+        } else {
+            let ratio = self.frequency * self.pitch_ratio / KEY_TRACK_REFERENCE_HZ;
+            self.cutoff * semitone_ratio(self.key_track * ratio_to_semitones(ratio))
+        };
+        self.filter.set_cutoff(cutoff);
+    }
+
+    /// Base frequency in Hz.
+    pub fn frequency(&self) -> f32 {
+        self.frequency
+    }
+
+    /// Select the oscillator's sound source.
+    pub fn set_waveform(&mut self, waveform: Waveform) {
+        self.osc.set_waveform(waveform);
+    }
+
+    /// Currently selected sound source.
+    pub fn waveform(&self) -> Waveform {
+        self.osc.waveform()
+    }
+
+    /// Select the sub-oscillator's sound source (square on startup).
+    pub fn set_sub_waveform(&mut self, waveform: Waveform) {
+        self.sub_osc.set_waveform(waveform);
+    }
+
+    /// Currently selected sub-oscillator sound source.
+    pub fn sub_waveform(&self) -> Waveform {
+        self.sub_osc.waveform()
+    }
+
+    /// Sub-oscillator mix level (0.0 to 1.0), not the smoothed gains.
+    pub fn sub_level(&self) -> f32 {
+        self.sub_level
+    }
+
+    /// Set the sub-oscillator mix level (0.0 = off, 1.0 = equal to the main oscillator).
+    ///
+    /// The sum is scaled by 1 / (1 + level), so adding the sub never pushes
+    /// the voice past full scale; at 0.0 the output is the main oscillator alone.
+    /// Both gains glide to the new mix like the volume does, so moving the level never clicks.
+    pub fn set_sub_level(&mut self, level: f32) {
+        self.sub_level = level.clamp(0.0, 1.0);
+        self.main_gain_target = 1.0 / (1.0 + self.sub_level);
+        self.sub_gain_target = self.sub_level * self.main_gain_target;
+    }
+
+    /// Set filter cutoff in Hz (FILTER_CUTOFF_MAX with no resonance = bypass).
+    ///
+    /// With key tracking this is the cutoff at KEY_TRACK_REFERENCE_HZ.
+    pub fn set_cutoff(&mut self, cutoff: f32) {
+        self.cutoff = cutoff;
+        self.update_filter_cutoff();
+    }
+
+    /// Filter cutoff in Hz as set (before key tracking and clamping).
+    pub fn cutoff(&self) -> f32 {
+        self.cutoff
+    }
+
+    /// Set filter key tracking (0.0 = fixed cutoff, 1.0 = cutoff moves 1:1 with pitch).
+    pub fn set_key_track(&mut self, amount: f32) {
+        self.key_track = amount.clamp(0.0, 1.0);
+        self.update_filter_cutoff();
+    }
+
+    /// Filter key tracking amount (0.0 to 1.0).
+    pub fn key_track(&self) -> f32 {
+        self.key_track
+    }
+
+    /// Set filter resonance (0.0 to 1.0).
+    pub fn set_resonance(&mut self, resonance: f32) {
+        self.filter.set_resonance(resonance);
+    }
+
+    /// Filter resonance (0.0 to 1.0).
+    pub fn resonance(&self) -> f32 {
+        self.filter.resonance()
+    }
+
+    /// Set square-wave pulse width (clamped to a safe 0.05–0.95 range).
+    pub fn set_pulse_width(&mut self, width: f32) {
+        self.osc.set_pulse_width(width);
+    }
+
+    /// Square-wave pulse width (after clamping).
+    pub fn pulse_width(&self) -> f32 {
+        self.osc.pulse_width()
+    }
+
+    /// Target volume (0.0 to 1.0), not the smoothed value.
+    pub fn volume(&self) -> f32 {
+        self.volume_target
     }
 
     /// Set target volume (0.0 to 1.0).
@@ -52,24 +276,335 @@ impl Voice {
 
     /// Set voice active state.
     /// true = voice plays, false = voice silent (but retains frequency/volume)
+    ///
+    /// The change ramps over FADE_MS instead of cutting. Toggling again mid-fade
+    /// retargets the ramp from its current gain, so it never jumps.
     pub fn set_active(&mut self, active: bool) {
         self.active = active;
     }
 
+    /// Switch the voice on or off by hand (see `Message::ToggleVoice`).
+    ///
+    /// Switching on plays at the set volume: the velocity of a note the voice
+    /// played earlier no longer applies (it glides back with the volume smoothing).
+    pub fn toggle(&mut self) {
+        if !self.active {
+            self.velocity_gain = 1.0;
+        }
+        self.set_active(!self.active);
+    }
+
+    /// Whether the voice produces sound: active, or still fading out.
+    pub fn is_sounding(&self) -> bool {
+        self.active || self.fade > 0.0
+    }
+
+    /// Anti-pop ramp gain currently applied (0.0 = silent, 1.0 = fully faded in).
+    pub fn fade(&self) -> f32 {
+        self.fade
+    }
+
+    /// Whether the anti-pop ramp is still moving (the fade gain changes every sample).
+    pub fn fading(&self) -> bool {
+        self.fade != if self.fades_in() { 1.0 } else { 0.0 }
+    }
+
+    /// Whether the fade ramps toward 1.0: switched on, let through by the
+    /// arpeggiator and not waiting for a stolen note to fade out.
+    #[inline(always)]
+    fn fades_in(&self) -> bool {
+        self.active && self.gate && self.pending.is_none()
+    }
+
+    /// Open or close the arpeggiator gate (closed = fades out over FADE_MS, stays active).
+    pub fn set_gate(&mut self, open: bool) {
+        self.gate = open;
+    }
+
+    /// Choose whether note_on restarts the oscillator phase (true) or continues it (legato).
+    pub fn set_retrigger(&mut self, retrigger: bool) {
+        self.retrigger = retrigger;
+    }
+
+    /// Whether note_on restarts the oscillator phase.
+    pub fn retrigger(&self) -> bool {
+        self.retrigger
+    }
+
+    /// Start playing a note at the given frequency and velocity.
+    ///
+    /// Velocity scales the voice's volume (127 = set volume, 1 = quiet but audible).
+    /// With retrigger enabled a note starting from silence restarts the phase so
+    /// every attack is identical; re-striking a sounding note keeps its phase,
+    /// since a reset there would jump the waveform.
+    ///
+    /// A voice still sounding another note is stolen: that note fades out over
+    /// FADE_MS first and this one starts from silence, so the waveform never
+    /// jumps. `note()` reports the new note right away, so its note_off
+    /// finds the voice even before it starts.
+    ///
+    /// # Arguments
+    /// * `note` - MIDI note number (remembered so the matching note_off releases it)
+    /// * `frequency` - Note frequency in Hz
+    /// * `velocity` - MIDI velocity (1-127)
+    pub fn note_on(&mut self, note: u8, frequency: f32, velocity: u8) {
+        let silent = !self.is_sounding();
+        let stolen = !silent && self.note != Some(note);
+        self.note = Some(note);
+        self.set_active(true);
+        if stolen || self.pending.is_some() {
+            self.pending = Some((frequency, velocity));
+        } else {
+            self.start_note(frequency, velocity, silent);
+        }
+    }
+
+    /// Retune and apply the velocity of a note.
+    ///
+    /// With retrigger the phase restarts, but only when `from_silence`.
+    fn start_note(&mut self, frequency: f32, velocity: u8, from_silence: bool) {
+        self.velocity_gain = velocity_to_gain(velocity);
+        self.set_frequency(frequency);
+        if self.retrigger && from_silence {
+            self.osc.reset_phase();
+            self.sub_osc.reset_phase();
+        }
+    }
+
+    /// Whether the main oscillator completed a cycle on the last tick (false while silent).
+    pub fn wrapped(&self) -> bool {
+        self.is_sounding() && self.osc.wrapped()
+    }
+
+    /// Hard-sync reset: restart both oscillators' cycles (keeps the sub an exact octave below).
+    pub fn sync_reset(&mut self) {
+        self.osc.reset_phase();
+        self.sub_osc.reset_phase();
+    }
+
+    /// MIDI note currently held (None = free for the allocator or not note-driven).
+    pub fn note(&self) -> Option<u8> {
+        self.note
+    }
+
+    /// Release the voice if it is holding `note`.
+    ///
+    /// # Returns
+    /// true if the voice was holding the note and is now inactive
+    pub fn note_off(&mut self, note: u8) -> bool {
+        if self.note != Some(note) {
+            return false;
+        }
+        // Released before a steal finished: the new note never starts
+        self.note = None;
+        self.pending = None;
+        self.set_active(false);
+        true
+    }
+
+    /// Peak output magnitude since the previous call, then reset.
+    ///
+    /// The peak is taken after volume smoothing, so it already reflects
+    /// the smoothed volume × velocity × fade × oscillator level (0.0 while silent).
+    pub fn take_peak(&mut self) -> f32 {
+        core::mem::take(&mut self.peak)
+    }
+
     /// Generate next audio sample.
     ///
+    /// # Arguments
+    /// * `user_tables` - User wavetable slots for `Waveform::Custom`
+    ///
     /// # Returns
-    /// Audio sample (-1.0 to 1.0) scaled by smoothed volume, or 0.0 if inactive
-    pub fn tick(&mut self) -> f32 {
-        
-        if self.active {
-            // Smooth volume using exponential moving average
-            // This eliminates zipper noise from instant parameter changes
-            self.volume_current = self.volume_current * VOLUME_SMOOTHING_COEFF
-                + self.volume_target * (1.0 - VOLUME_SMOOTHING_COEFF);
-            self.osc.tick() * self.volume_current
+    /// Audio sample (-1.0 to 1.0) scaled by smoothed volume, or 0.0 once silent
+    pub fn tick(&mut self, user_tables: &[Wavetable]) -> f32 {
+        if self.is_sounding() {
+            self.next_sample(user_tables)
         } else {
             0.0
         }
     }
+
+    /// Accumulate a block of samples into `out`.
+    ///
+    /// Block counterpart of `tick()`: adds the same samples `tick()` would return,
+    /// so silent voices leave `out` untouched and cost nothing.
+    ///
+    /// # Arguments
+    /// * `out` - Mix buffer to add into
+    /// * `user_tables` - User wavetable slots for `Waveform::Custom`
+    pub fn render_add(&mut self, out: &mut [f32], user_tables: &[Wavetable]) {
+        if !self.is_sounding() {
+            return;
+        }
+
+        for sample in out.iter_mut() {
+            *sample += self.next_sample(user_tables);
+        }
+    }
+
+    /// Advance smoothing, fade and oscillator by one sample (sounding path of `tick()`).
+    #[inline(always)]
+    fn next_sample(&mut self, user_tables: &[Wavetable]) -> f32 {
+        // Smooth volume using exponential moving average
+        // This eliminates zipper noise from instant parameter changes
+        let target = self.volume_target * self.velocity_gain;
+        self.volume_current = self.volume_current * VOLUME_SMOOTHING_COEFF
+            + target * (1.0 - VOLUME_SMOOTHING_COEFF);
+        let mut sample = self.osc.tick_with(user_tables);
+        // The sub runs while it's mixed in or still gliding out
+        if self.sub_gain_target > 0.0 || self.sub_gain_current > 0.0 {
+            self.main_gain_current = smooth_gain(self.main_gain_current, self.main_gain_target);
+            self.sub_gain_current = smooth_gain(self.sub_gain_current, self.sub_gain_target);
+            sample = sample * self.main_gain_current
+                + self.sub_osc.tick_with(user_tables) * self.sub_gain_current;
+        }
+        // Linear anti-pop ramp toward 1.0 (active) or 0.0 (switched off or gated)
+        if self.fades_in() {
+            if self.fade < 1.0 {
+                self.fade = (self.fade + self.fade_step).min(1.0);
+            }
+        } else {
+            self.fade = (self.fade - self.fade_step).max(0.0);
+            // A stolen note has faded out; the note that stole the voice starts here
+            if self.fade == 0.0 {
+                if let Some((frequency, velocity)) = self.pending.take() {
+                    self.start_note(frequency, velocity, true);
+                }
+            }
+        }
+
+        let sample = self.filter.process(sample) * self.volume_current * self.fade;
+        self.peak = self.peak.max(sample).max(-sample);
+        sample
+    }
+}
+
+/// Step a sub-mix gain toward its target with the volume smoothing, snapping on once close.
+#[inline(always)]
+fn smooth_gain(current: f32, target: f32) -> f32 {
+    let next = current * VOLUME_SMOOTHING_COEFF + target * (1.0 - VOLUME_SMOOTHING_COEFF);
+    let diff = next - target;
+    if diff.max(-diff) < SUB_GAIN_SNAP {
+        target
+    } else {
+        next
+    }
+}
+
+/// Map MIDI velocity to an amplitude scale with a squared (more natural) response.
+///
+/// 127 → exactly 1.0 (the set volume), 1 → just above VELOCITY_MIN_GAIN so soft
+/// notes stay audible.
+fn velocity_to_gain(velocity: u8) -> f32 {
+    let v = velocity.min(VELOCITY_MAX) as f32 / VELOCITY_MAX as f32;
+    // Written as 1 - (...) so full velocity doesn't round to a hair off unity
+    1.0 - (1.0 - VELOCITY_MIN_GAIN) * (1.0 - v * v)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE_RATE: f32 = 48_000.0;
+
+    /// Voice switched on and run until its fade-in and volume smoothing settled.
+    fn settled_voice() -> Voice {
+        let mut voice = Voice::new(440.0, SAMPLE_RATE, 1);
+        voice.set_active(true);
+        for _ in 0..SAMPLE_RATE as usize / 10 {
+            voice.tick(&[]);
+        }
+        voice
+    }
+
+    #[test]
+    fn sub_level_changes_glide() {
+        let mut voice = settled_voice();
+        voice.set_sub_level(1.0);
+
+        let mut largest_step = 0.0f32;
+        for _ in 0..SAMPLE_RATE as usize / 10 {
+            let (main, sub) = (voice.main_gain_current, voice.sub_gain_current);
+            voice.tick(&[]);
+            largest_step = largest_step
+                .max((voice.main_gain_current - main).abs())
+                .max((voice.sub_gain_current - sub).abs());
+        }
+
+        // A step straight to the new mix would move each gain by 0.5
+        assert!(largest_step <= 0.5 * (1.0 - VOLUME_SMOOTHING_COEFF) + 1e-6, "step {largest_step}");
+        assert_eq!((voice.main_gain_current, voice.sub_gain_current), (0.5, 0.5));
+    }
+
+    #[test]
+    fn sub_switched_off_leaves_the_main_oscillator_alone() {
+        let mut with_sub = settled_voice();
+        let mut without_sub = settled_voice();
+        with_sub.set_sub_level(0.7);
+        for _ in 0..SAMPLE_RATE as usize / 10 {
+            with_sub.tick(&[]);
+            without_sub.tick(&[]);
+        }
+
+        with_sub.set_sub_level(0.0);
+        for _ in 0..SAMPLE_RATE as usize / 10 {
+            with_sub.tick(&[]);
+            without_sub.tick(&[]);
+        }
+
+        assert_eq!(with_sub.sub_gain_current, 0.0);
+        for _ in 0..1000 {
+            assert_eq!(with_sub.tick(&[]), without_sub.tick(&[]));
+        }
+    }
+
+    #[test]
+    fn releasing_a_pending_steal_never_starts_it() {
+        let mut voice = settled_voice();
+        voice.note_on(60, 261.6, 127);
+        voice.note_on(72, 523.3, 127);
+        assert_eq!(voice.note(), Some(72));
+
+        // Released mid fade-out: the voice falls silent still tuned to the old note
+        assert!(voice.note_off(72));
+        for _ in 0..SAMPLE_RATE as usize / 10 {
+            voice.tick(&[]);
+        }
+        assert!(!voice.is_sounding());
+        assert_eq!(voice.frequency(), 440.0);
+    }
+
+    #[test]
+    fn full_velocity_plays_at_the_set_volume() {
+        let mut struck = Voice::new(440.0, SAMPLE_RATE, 1);
+        let mut switched = Voice::new(440.0, SAMPLE_RATE, 1);
+        struck.note_on(69, 440.0, VELOCITY_MAX);
+        switched.set_active(true);
+        for _ in 0..SAMPLE_RATE as usize / 10 {
+            assert_eq!(struck.tick(&[]).to_bits(), switched.tick(&[]).to_bits());
+        }
+    }
+
+    #[test]
+    fn toggling_on_after_a_soft_note_plays_at_the_set_volume() {
+        let mut voice = Voice::new(440.0, SAMPLE_RATE, 1);
+        voice.note_on(69, 440.0, 20);
+        for _ in 0..SAMPLE_RATE as usize / 10 {
+            voice.tick(&[]);
+        }
+        voice.note_off(69);
+        for _ in 0..SAMPLE_RATE as usize / 10 {
+            voice.tick(&[]);
+        }
+
+        voice.toggle();
+        for _ in 0..SAMPLE_RATE as usize / 10 {
+            voice.tick(&[]);
+        }
+        let settled = settled_voice();
+        assert_eq!(voice.velocity_gain, 1.0);
+        // The smoothing stalls a few ulps short when gliding up; the soft note sat near 0.03
+        assert!((voice.volume_current - settled.volume_current).abs() < 1e-4);
+    }
 }